@@ -0,0 +1,448 @@
+use crate::model::ThemePreset;
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: wiza-rs [OPTIONS] [STEPS_YAML]
+
+Arguments:
+  [STEPS_YAML]        Path to the steps file (default: $WIZA_FILE, else the first
+                      of ./steps.yaml, ./.wiza.yaml and
+                      $XDG_CONFIG_HOME/wiza/steps.yaml that exists)
+
+Options:
+  --events <PATH>     Append JSON-lines step events to PATH
+  --report <PATH>     Write a JSON report of every step (status, commands and
+                      their exit codes, log) to PATH when the run ends
+  -y, --yes           Skip the confirmation screen and start right away
+  --select            Start with a checklist of the steps to run in this session;
+                      unticked steps are skipped
+  --reset-once        Forget which run_once steps of this file have run, so
+                      they run again
+  --continue          Mark the steps that succeeded in the last run of this
+                      file as done and start at the first one that did not
+  --yaml-schema       Print the JSON Schema of the steps file and exit
+  --print-config <FORMAT>
+                      Print the parsed steps file as yaml or json and exit
+  --check             Validate STEPS_YAML, print the problems as JSON and exit
+  --strict            Run extra checks before starting (e.g. `bash -n` on scripts)
+  --no-syntax-check   Skip the `bash -n` check under --strict
+  --deny-warnings     Treat validation warnings as errors: abort before running
+                      (and make --check fail) if there are any
+  --verbose           Add `[wiza]` lines about what the executor does (shell,
+                      working directory, vars, timeouts, retries) to the logs
+  --dry-run           Log what each step would do (file edits as diffs) without
+                      running commands or writing files
+  --env-file <PATH>   Load KEY=VALUE vars from a dotenv file (overrides `env_file`)
+  --base-dir <DIR>    Resolve relative paths in step params against DIR
+                      (default: the directory containing STEPS_YAML)
+  --max-runtime <SECS>
+                      Abort the whole run after SECS seconds (exit code 124)
+  --sudo-timeout <SECS>
+                      Give up on the startup `sudo -v` after SECS seconds and go
+                      on without it (default: 60, 0 = wait forever)
+  --step-timeout-default <SECS>
+                      Kill step commands after SECS seconds unless the step sets
+                      `timeout_secs` (default: no limit)
+  --jobs <N>          Run up to N independent steps at once in run-all mode (`a`)
+                      (default: 1)
+  --fail-fast         When a failure stops the run-all batch, mark the steps it
+                      did not reach as Not run instead of leaving them Pending
+  --max-line-len <N>  Truncate log lines longer than N characters (default: 4000,
+                      0 = never)
+  --full-log <PATH>   Append the untruncated output of every command to PATH
+  --max-log-lines <N> Keep only the last N lines of each step log
+  --max-log-kb <N>    Keep only the last N KB of each step log in memory and the
+                      rest in a temp file (`o` opens it)
+  --split <PERCENT>   Width of the steps pane in percent, 15 to 70 (default: 35)
+  --no-color          Plain text without colours (also when NO_COLOR is set)
+  --theme <PRESET>    Look of the TUI: dark (default), light, mono or ascii;
+                      overrides the steps file's theme preset
+  --no-alt-screen     Draw the TUI on the normal screen so it stays in the scrollback
+  --headless          Run every step without the TUI and print the logs
+  --run-step <NAME>   Run only the step NAME headless, print its log and exit
+                      with its status
+  --with-deps         With --run-step, first run the steps NAME depends on
+  --format <FORMAT>   Headless output: plain (default) or ci
+  --apps <POLICY>     Headless app_selection: all, none or defaults
+                      (default: skip those steps)
+  --notify            Send a desktop notification when the run finishes
+  -h, --help          Print this help
+";
+
+/// Output style of the headless runner (`--format`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Step logs as they are, one after the other.
+    #[default]
+    Plain,
+    /// Every line prefixed with its step name, plus a summary table.
+    Ci,
+}
+
+/// Which apps the headless runner installs for app_selection steps (`--apps`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppsPolicy {
+    All,
+    None,
+    /// The apps marked `default: true`.
+    Defaults,
+}
+
+/// Output of `--print-config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+}
+
+/// Command-line options.
+#[derive(Debug, Clone)]
+pub struct Cli {
+    pub yaml_path: String,
+    /// Where the steps file was looked for, in order, when none was given.
+    pub yaml_search: Option<Vec<String>>,
+    /// Where to append JSON-lines status events, if anywhere.
+    pub events: Option<String>,
+    /// Where to write the JSON report when the run ends, if anywhere.
+    pub report: Option<String>,
+    /// Log executor internals as `[wiza]` lines.
+    pub verbose: bool,
+    /// Skip the intro/confirmation screen.
+    pub yes: bool,
+    /// Clear the `run_once` markers of the steps file before running.
+    pub reset_once: bool,
+    /// Tick the steps to run in a checklist before starting.
+    pub select: bool,
+    /// Pick up the saved progress of the last run (`--continue`).
+    pub resume: bool,
+    /// Print the steps-file JSON Schema instead of running.
+    pub yaml_schema: bool,
+    /// Print the parsed steps file instead of running.
+    pub print_config: Option<ConfigFormat>,
+    /// Validate and report as JSON instead of running.
+    pub check: bool,
+    /// Budget for the whole run, in seconds.
+    pub max_runtime: Option<u64>,
+    /// Dotenv file to load vars from, instead of the steps file's `env_file`.
+    pub env_file: Option<String>,
+    /// Overrides the directory relative step paths are resolved against.
+    pub base_dir: Option<String>,
+    /// Enable the stricter, slower validation pass.
+    pub strict: bool,
+    /// Leave the `bash -n` check out of the strict pass.
+    pub no_syntax_check: bool,
+    /// Fail on validation warnings as if they were errors.
+    pub deny_warnings: bool,
+    /// Log commands and file edits instead of carrying them out.
+    pub dry_run: bool,
+    /// Time limit of the startup `sudo -v`, in seconds.
+    pub sudo_timeout: Option<u64>,
+    /// Time limit of every step command, unless the step sets its own.
+    pub step_timeout_default: Option<u64>,
+    /// Worker limit for the "run all" batch mode.
+    pub jobs: usize,
+    /// Mark the steps a failed batch did not reach as `NotRun`.
+    pub fail_fast: bool,
+    /// Cap on the length of a stored log line.
+    pub max_line_len: Option<usize>,
+    /// Where to keep the untruncated command output.
+    pub full_log: Option<String>,
+    /// Line limit of each step log.
+    pub max_log_lines: Option<usize>,
+    /// In-memory size limit of each step log, in KB.
+    pub max_log_kb: Option<usize>,
+    /// Initial width of the TUI steps pane, in percent.
+    pub split: Option<u16>,
+    /// Strip colours from the TUI and CI output.
+    pub no_color: bool,
+    /// `--theme`: preset used instead of the steps file's `theme.preset`.
+    pub theme: Option<ThemePreset>,
+    /// Keep the TUI off the alternate screen.
+    pub no_alt_screen: bool,
+    /// Run without the TUI.
+    pub headless: bool,
+    /// Run just this step, headless (`--run-step`).
+    pub run_step: Option<String>,
+    /// Include the `depends_on` chain of `run_step`.
+    pub with_deps: bool,
+    pub format: OutputFormat,
+    /// App selection in headless mode; app_selection steps are skipped without it.
+    pub apps: Option<AppsPolicy>,
+    /// Send a desktop notification once all steps are done or on quit.
+    pub notify: bool,
+    pub help: bool,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self {
+            yaml_path: "steps.yaml".to_string(),
+            yaml_search: None,
+            events: None,
+            report: None,
+            resume: false,
+            select: false,
+            reset_once: false,
+            verbose: false,
+            yes: false,
+            yaml_schema: false,
+            print_config: None,
+            check: false,
+            max_runtime: None,
+            env_file: None,
+            base_dir: None,
+            strict: false,
+            no_syntax_check: false,
+            deny_warnings: false,
+            dry_run: false,
+            sudo_timeout: None,
+            step_timeout_default: None,
+            jobs: 1,
+            fail_fast: false,
+            max_line_len: None,
+            full_log: None,
+            max_log_lines: None,
+            max_log_kb: None,
+            split: None,
+            no_color: false,
+            theme: None,
+            no_alt_screen: false,
+            headless: false,
+            run_step: None,
+            with_deps: false,
+            format: OutputFormat::Plain,
+            apps: None,
+            notify: false,
+            help: false,
+        }
+    }
+}
+
+impl Cli {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Whether output may use colour: not with `--no-color` or a non-empty
+    /// `NO_COLOR` (https://no-color.org).
+    pub fn color(&self) -> bool {
+        !self.no_color
+            && self.theme != Some(ThemePreset::Mono)
+            && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+    }
+
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = Cli::default();
+        let mut yaml_path = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => cli.help = true,
+                "--events" => cli.events = Some(value(&mut args, &arg)?),
+                "--report" => cli.report = Some(value(&mut args, &arg)?),
+                "--continue" => cli.resume = true,
+                "--select" => cli.select = true,
+                "--reset-once" => cli.reset_once = true,
+                "--verbose" => cli.verbose = true,
+                "-y" | "--yes" => cli.yes = true,
+                "--yaml-schema" => cli.yaml_schema = true,
+                "--print-config" => {
+                    cli.print_config = Some(match value(&mut args, &arg)?.as_str() {
+                        "yaml" => ConfigFormat::Yaml,
+                        "json" => ConfigFormat::Json,
+                        other => {
+                            return Err(anyhow!(
+                                "Unknown config format '{}' (expected yaml or json)\n\n{}",
+                                other,
+                                USAGE
+                            ));
+                        }
+                    });
+                }
+                "--check" => cli.check = true,
+                "--apps" => {
+                    cli.apps = Some(match value(&mut args, &arg)?.as_str() {
+                        "all" => AppsPolicy::All,
+                        "none" => AppsPolicy::None,
+                        "defaults" => AppsPolicy::Defaults,
+                        other => {
+                            return Err(anyhow!(
+                                "Unknown apps policy '{}' (expected all, none or defaults)\n\n{}",
+                                other,
+                                USAGE
+                            ));
+                        }
+                    });
+                }
+                "--max-runtime" => cli.max_runtime = Some(number(&mut args, &arg)?),
+                "--env-file" => cli.env_file = Some(value(&mut args, &arg)?),
+                "--base-dir" => cli.base_dir = Some(value(&mut args, &arg)?),
+                "--strict" => cli.strict = true,
+                "--no-syntax-check" => cli.no_syntax_check = true,
+                "--deny-warnings" => cli.deny_warnings = true,
+                "--dry-run" => cli.dry_run = true,
+                "--sudo-timeout" => cli.sudo_timeout = Some(number(&mut args, &arg)?),
+                "--step-timeout-default" => {
+                    cli.step_timeout_default = Some(number(&mut args, &arg)?)
+                }
+                "--fail-fast" => cli.fail_fast = true,
+                "--jobs" => {
+                    cli.jobs = number(&mut args, &arg)?;
+                    if cli.jobs == 0 {
+                        return Err(anyhow!("Option '--jobs' must be at least 1\n\n{}", USAGE));
+                    }
+                }
+                "--split" => {
+                    let split = number(&mut args, &arg)?;
+                    if !(crate::tui::MIN_SPLIT..=crate::tui::MAX_SPLIT).contains(&split) {
+                        return Err(anyhow!(
+                            "Option '--split' must be between {} and {}\n\n{}",
+                            crate::tui::MIN_SPLIT,
+                            crate::tui::MAX_SPLIT,
+                            USAGE
+                        ));
+                    }
+                    cli.split = Some(split);
+                }
+                "--max-line-len" => cli.max_line_len = Some(number(&mut args, &arg)?),
+                "--full-log" => cli.full_log = Some(value(&mut args, &arg)?),
+                "--max-log-lines" => {
+                    let lines = number(&mut args, &arg)?;
+                    if lines == 0 {
+                        return Err(anyhow!(
+                            "Option '--max-log-lines' must be at least 1\n\n{}",
+                            USAGE
+                        ));
+                    }
+                    cli.max_log_lines = Some(lines);
+                }
+                "--max-log-kb" => {
+                    let kb = number(&mut args, &arg)?;
+                    if kb == 0 {
+                        return Err(anyhow!(
+                            "Option '--max-log-kb' must be at least 1\n\n{}",
+                            USAGE
+                        ));
+                    }
+                    cli.max_log_kb = Some(kb);
+                }
+                "--no-color" => cli.no_color = true,
+                "--theme" => {
+                    cli.theme = Some(match value(&mut args, &arg)?.as_str() {
+                        "dark" | "default" => ThemePreset::Default,
+                        "light" => ThemePreset::Light,
+                        "mono" => ThemePreset::Mono,
+                        "ascii" => ThemePreset::Ascii,
+                        other => {
+                            return Err(anyhow!(
+                                "Unknown theme '{}' (expected dark, light, mono or ascii)\n\n{}",
+                                other,
+                                USAGE
+                            ));
+                        }
+                    });
+                }
+                "--no-alt-screen" => cli.no_alt_screen = true,
+                "--headless" => cli.headless = true,
+                "--run-step" => cli.run_step = Some(value(&mut args, &arg)?),
+                "--with-deps" => cli.with_deps = true,
+                "--format" => {
+                    cli.format = match value(&mut args, &arg)?.as_str() {
+                        "plain" => OutputFormat::Plain,
+                        "ci" => OutputFormat::Ci,
+                        other => {
+                            return Err(anyhow!(
+                                "Unknown format '{}' (expected plain or ci)\n\n{}",
+                                other,
+                                USAGE
+                            ));
+                        }
+                    }
+                }
+                "--notify" => cli.notify = true,
+                flag if flag.starts_with('-') => {
+                    return Err(anyhow!("Unknown option '{}'\n\n{}", flag, USAGE));
+                }
+                _ => {
+                    if yaml_path.replace(arg).is_some() {
+                        return Err(anyhow!("Only one steps file may be given\n\n{}", USAGE));
+                    }
+                }
+            }
+        }
+
+        match yaml_path {
+            Some(path) => cli.yaml_path = path,
+            None => {
+                let candidates = steps_file_candidates();
+                if let Some(found) = candidates.iter().find(|path| Path::new(path).is_file()) {
+                    cli.yaml_path = found.clone();
+                }
+                cli.yaml_search = Some(candidates);
+            }
+        }
+        if cli.with_deps && cli.run_step.is_none() {
+            return Err(anyhow!("Option '--with-deps' requires --run-step\n\n{}", USAGE));
+        }
+        // A one-shot run-and-report has no use for the TUI.
+        cli.headless |= cli.run_step.is_some();
+        Ok(cli)
+    }
+
+    /// Contents of the steps file. When none was given and the search found
+    /// nothing, the error lists where it looked.
+    pub fn read_steps_file(&self) -> Result<String> {
+        match &self.yaml_search {
+            Some(candidates) if !Path::new(&self.yaml_path).is_file() => Err(anyhow!(
+                "No steps file found; looked for:\n  {}\nPass a path or set WIZA_FILE.",
+                candidates.join("\n  ")
+            )),
+            _ => std::fs::read_to_string(&self.yaml_path)
+                .with_context(|| format!("Failed to read {}", self.yaml_path)),
+        }
+    }
+
+    /// Directory used for relative paths: `--base-dir`, or the steps file's parent.
+    pub fn base_dir(&self) -> PathBuf {
+        let dir = match &self.base_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => match Path::new(&self.yaml_path).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            },
+        };
+        dir.canonicalize().unwrap_or(dir)
+    }
+}
+
+/// Where to look for the steps file when none is given: only `$WIZA_FILE`
+/// when it is set, else the current directory, then the config directory.
+fn steps_file_candidates() -> Vec<String> {
+    if let Some(file) = std::env::var_os("WIZA_FILE").filter(|file| !file.is_empty()) {
+        return vec![file.to_string_lossy().into_owned()];
+    }
+    let mut candidates = vec!["steps.yaml".to_string(), ".wiza.yaml".to_string()];
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    };
+    if let Some(config) = config {
+        candidates.push(config.join("wiza/steps.yaml").to_string_lossy().into_owned());
+    }
+    candidates
+}
+
+/// Fetch the value following `flag` and parse it as a number.
+fn number<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<T> {
+    let raw = value(args, flag)?;
+    raw.parse()
+        .map_err(|_| anyhow!("Option '{}' expects a number, got '{}'\n\n{}", flag, raw, USAGE))
+}
+
+/// Fetch the value following `flag`.
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    args.next()
+        .ok_or_else(|| anyhow!("Option '{}' requires a value\n\n{}", flag, USAGE))
+}
@@ -0,0 +1,63 @@
+use crate::model::on_path;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Put `text` on the clipboard with the first available tool (`wl-copy`,
+/// `xclip`, `xsel`, `pbcopy`). Without one, fall back to the OSC 52 escape
+/// sequence, which most terminal emulators (also over SSH) turn into a
+/// clipboard write. Returns how the text was copied.
+pub fn copy(text: &str) -> Result<&'static str, String> {
+    let tools: [(&str, &[&str]); 4] = [
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("pbcopy", &[]),
+    ];
+    let display = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var_os("DISPLAY").is_some();
+    for (program, args) in tools {
+        let needs_display = program != "pbcopy";
+        if (needs_display && !display) || !on_path(program) {
+            continue;
+        }
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        match child.wait() {
+            Ok(status) if status.success() => return Ok(program),
+            _ => continue,
+        }
+    }
+
+    let mut out = std::io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Failed to write to the terminal: {}", e))?;
+    Ok("the terminal (OSC 52)")
+}
+
+/// Standard base64 with padding, as OSC 52 expects.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,96 @@
+use crate::model::Vars;
+use anyhow::{anyhow, Context, Result};
+
+/// Vars read from a dotenv file, plus notes about lines that were ignored.
+pub struct EnvFile {
+    pub vars: Vars,
+    pub skipped: Vec<String>,
+}
+
+/// Load `KEY=VALUE` lines from `path`. Blank lines and `#` comments are
+/// ignored, `export ` prefixes are allowed, and values may be single-quoted
+/// (taken literally) or double-quoted (with `\n`, `\"` and `\\` escapes).
+/// Malformed lines are an error when `strict`, otherwise they are skipped.
+pub fn load(path: &str, strict: bool) -> Result<EnvFile> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file: {}", path))?;
+    let mut env = EnvFile {
+        vars: Vars::new(),
+        skipped: Vec::new(),
+    };
+    for (idx, line) in text.lines().enumerate() {
+        match parse_line(line) {
+            Ok(Some((key, value))) => {
+                env.vars.insert(key, value);
+            }
+            Ok(None) => {}
+            Err(e) if strict => return Err(anyhow!("{}:{}: {}", path, idx + 1, e)),
+            Err(e) => env.skipped.push(format!("{}:{}: {}; line ignored", path, idx + 1, e)),
+        }
+    }
+    Ok(env)
+}
+
+fn parse_line(line: &str) -> Result<Option<(String, String)>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, raw) = line
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected KEY=VALUE"))?;
+    let key = key.trim();
+    let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return Err(anyhow!("invalid variable name '{}'", key));
+    }
+    Ok(Some((key.to_string(), parse_value(raw.trim())?)))
+}
+
+fn parse_value(raw: &str) -> Result<String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let (value, trailing) = rest
+            .split_once('\'')
+            .ok_or_else(|| anyhow!("unterminated single quote"))?;
+        check_trailing(trailing)?;
+        return Ok(value.to_string());
+    }
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    check_trailing(chars.as_str())?;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        return Err(anyhow!("unterminated double quote"));
+    }
+    // Unquoted: an inline comment starts at ` #`.
+    let value = match raw.find(" #") {
+        Some(idx) => &raw[..idx],
+        None => raw,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+/// Only whitespace or a comment may follow a closing quote.
+fn check_trailing(trailing: &str) -> Result<()> {
+    let trailing = trailing.trim();
+    if trailing.is_empty() || trailing.starts_with('#') {
+        Ok(())
+    } else {
+        Err(anyhow!("unexpected text after closing quote"))
+    }
+}
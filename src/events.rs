@@ -0,0 +1,54 @@
+use crate::model::StepStatus;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// JSON-lines sink for step status transitions (`--events <path>`).
+pub struct EventSink {
+    pub path: String,
+    file: File,
+}
+
+impl EventSink {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open events file: {}", path))?;
+        Ok(Self {
+            path: path.to_string(),
+            file,
+        })
+    }
+
+    /// Append one event describing `step` entering `status`.
+    pub fn emit(&mut self, index: usize, step: &str, status: StepStatus) -> Result<()> {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        let event = json!({
+            "ts": ts,
+            "event": event_name(status),
+            "index": index,
+            "step": step,
+        });
+        writeln!(self.file, "{}", event)
+            .and_then(|_| self.file.flush())
+            .with_context(|| format!("Failed to write event to {}", self.path))
+    }
+}
+
+fn event_name(status: StepStatus) -> &'static str {
+    match status {
+        StepStatus::Pending => "reset",
+        StepStatus::Running => "started",
+        StepStatus::Skipped => "skipped",
+        StepStatus::Success => "finished",
+        StepStatus::Failed => "failed",
+        StepStatus::NotRun => "not_run",
+    }
+}
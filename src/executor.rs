@@ -1,161 +1,2340 @@
+use crate::cli::Cli;
+use crate::dotenv;
 use crate::model::*;
+use crate::sha256;
+use crate::state::OnceMarkers;
 use anyhow::{anyhow, Context, Result};
-use std::process::{Command, ExitStatus, Output};
+use once_cell::sync::OnceCell;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+/// Placeholder written to logs instead of secret values.
+pub const REDACTED: &str = "••••••";
+
+/// State shared by all steps of a run.
+#[derive(Debug, Default, Clone)]
+pub struct RunContext {
+    /// Global vars map used for `${var}` substitution; prompt steps write into it.
+    pub vars: Vars,
+    /// Names of vars holding secrets; their values never reach the logs.
+    pub secrets: HashSet<String>,
+    /// Directory that relative paths in step params are resolved against.
+    pub base_dir: PathBuf,
+    /// `--strict`: problems that are warnings otherwise are errors.
+    pub strict: bool,
+    /// Where `run_once` steps are recorded, if there is a place for it.
+    pub once: Option<OnceMarkers>,
+}
+
+impl RunContext {
+    pub fn new(vars: Vars) -> Self {
+        Self {
+            vars,
+            secrets: HashSet::new(),
+            base_dir: PathBuf::from("."),
+            strict: false,
+            once: None,
+        }
+    }
+
+    /// Context for a run of `steps_file`: its `vars`, the base dir from `cli`,
+    /// and the env file, if any. Setup notes are appended to `log`.
+    pub fn prepare(steps_file: &StepFile, cli: &Cli, log: &mut String) -> Result<Self> {
+        let mut ctx = Self::new(steps_file.vars.clone());
+        if cli.yaml_search.is_some() {
+            log.push_str(&format!("Using steps file {}\n", cli.yaml_path));
+        }
+        ctx.base_dir = cli.base_dir();
+        ctx.strict = cli.strict;
+        ctx.once = OnceMarkers::for_file(&cli.yaml_path);
+        log.push_str(&format!(
+            "Relative paths resolve against {}\n",
+            ctx.base_dir.display()
+        ));
+        log_verbose(log, || {
+            let cwd = std::env::current_dir().map(|dir| dir.display().to_string());
+            format!("working directory: {}", cwd.unwrap_or_else(|e| e.to_string()))
+        });
+        log_verbose(log, || {
+            let timeout = STEP_TIMEOUT_DEFAULT.get();
+            format!(
+                "dry run: {}, default step timeout: {}",
+                dry_run(),
+                timeout.map_or("none".to_string(), |secs| format!("{}s", secs))
+            )
+        });
+        // `--env-file` is taken as given; the steps file's `env_file` is relative to base_dir.
+        let env_file = cli
+            .env_file
+            .clone()
+            .or_else(|| steps_file.env_file.as_deref().map(|path| ctx.resolve_path(path)));
+        if let Some(path) = env_file {
+            let env = dotenv::load(&path, cli.strict)?;
+            log.push_str(&format!("Loaded {} var(s) from {}\n", env.vars.len(), path));
+            for note in env.skipped {
+                log.push_str(&format!("[env_file] {}\n", note));
+            }
+            ctx.vars.extend(env.vars);
+        }
+        log_verbose(log, || format!("vars: {}", var_names(&ctx.vars)));
+        Ok(ctx)
+    }
+
+    /// Resolve a path from step params against `base_dir`.
+    /// Absolute and `~`-prefixed paths are returned unchanged.
+    pub fn resolve_path(&self, path: &str) -> String {
+        resolve_against(&self.base_dir, path)
+    }
+
+    /// Text of a script field: the inline snippet, or the contents of its
+    /// `*_file`, with `${var}` references substituted.
+    fn script_text(&self, inline: Option<&str>, file: Option<&str>) -> Result<Option<String>> {
+        let text = match (inline, file) {
+            (Some(text), _) => text.to_string(),
+            (None, Some(file)) => {
+                let path = self.resolve_path(file);
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read script file {}", path))?
+            }
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(interpolate(&text, &self.vars)))
+    }
+
+    /// Replace every secret value occurring in `text` with a placeholder.
+    pub fn redact(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for name in &self.secrets {
+            if let Some(value) = self.vars.get(name) {
+                if !value.is_empty() {
+                    out = out.replace(value.as_str(), REDACTED);
+                }
+            }
+        }
+        out
+    }
+
+    /// Redact secrets from a step's log and command records.
+    pub fn redact_runtime(&self, runtime: &mut StepRuntime) {
+        if self.secrets.is_empty() {
+            return;
+        }
+        runtime.log = self.redact(&runtime.log);
+        for record in &mut runtime.commands {
+            record.command = self.redact(&record.command);
+            record.stdout = self.redact(&record.stdout);
+            record.stderr = self.redact(&record.stderr);
+        }
+    }
+}
+
+/// Hard deadline for the whole run (`--max-runtime`), set once at startup.
+static DEADLINE: OnceCell<Instant> = OnceCell::new();
+
+/// Returned when `--max-runtime` runs out; the running child is killed.
+#[derive(Debug, thiserror::Error)]
+#[error("global timeout reached (--max-runtime)")]
+pub struct GlobalTimeout;
+
+/// Start the global runtime budget. Only the first call has any effect.
+pub fn set_max_runtime(budget: Duration) {
+    let _ = DEADLINE.set(Instant::now() + budget);
+}
+
+/// Whether the `--max-runtime` budget has been used up.
+pub fn deadline_reached() -> bool {
+    DEADLINE.get().is_some_and(|deadline| Instant::now() >= *deadline)
+}
+
+/// Set by the SIGINT handler; consumed by the command being waited on.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Returned when Ctrl-C stops the running command.
+#[derive(Debug, thiserror::Error)]
+#[error("interrupted by Ctrl-C")]
+pub struct Interrupted;
+
+/// Keep SIGINT from killing wiza-rs while a command has the terminal, so
+/// Ctrl-C only aborts that command and the TUI gets restored normally.
+pub fn install_interrupt_handler() -> Result<()> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+        .context("Failed to install the Ctrl-C handler")
+}
+
+/// Forget a Ctrl-C that arrived while no command was running.
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Time limit for each step command (`--step-timeout-default`), for steps
+/// without their own `timeout_secs`; 0 = none.
+static STEP_TIMEOUT_DEFAULT: OnceCell<u64> = OnceCell::new();
+
+pub fn set_step_timeout_default(secs: u64) {
+    let _ = STEP_TIMEOUT_DEFAULT.set(secs);
+}
+
+/// How long each command of `step` may run: its `timeout_secs`, else
+/// `--step-timeout-default`. 0 at either level means no limit.
+fn step_timeout(step: &Step) -> Option<Duration> {
+    match step.timeout_secs.or_else(|| STEP_TIMEOUT_DEFAULT.get().copied()) {
+        None | Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// `--dry-run`: commands are logged instead of run and file edits are shown
+/// as diffs instead of written.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_dry_run() {
+    DRY_RUN.store(true, Ordering::SeqCst);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// `--verbose`: the logs get extra `[wiza]` lines about what the executor
+/// does (shell, working directory, vars, timeouts, retries).
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbose() {
+    VERBOSE.store(true, Ordering::SeqCst);
+}
+
+/// Add a `[wiza]` line to `log`, only under `--verbose`.
+fn log_verbose(log: &mut String, message: impl FnOnce() -> String) {
+    if VERBOSE.load(Ordering::SeqCst) {
+        log.push_str(&format!("[wiza] {}\n", message()));
+    }
+}
+
+/// How `shell` runs a command, for `--verbose`.
+fn shell_description(run_as: Option<&str>) -> String {
+    match run_as {
+        Some(user) => format!("sudo -u {} -- bash -c", user),
+        None => "bash -c".to_string(),
+    }
+}
+
+/// Sorted names (never values) of `vars`, for `--verbose`.
+fn var_names(vars: &Vars) -> String {
+    let mut names: Vec<&str> = vars.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    if names.is_empty() {
+        return "none".to_string();
+    }
+    names.join(", ")
+}
+
+/// Log `cmd` as skipped by `--dry-run`.
+fn log_dry_run(log: &mut String, cmd: &str) {
+    log.push_str(&format!("\n$ {}\n(dry run; not executed)\n", cmd));
+}
+
+/// Returned when a command runs past its own time limit; it is terminated.
+#[derive(Debug, thiserror::Error)]
+#[error("timed out after {}s", .0.as_secs())]
+pub struct CommandTimeout(pub Duration);
+
+/// Most of a live log kept while a command runs; older output is dropped
+/// from the front. The step's own log still gets all of it.
+const LIVE_LOG_LIMIT: usize = 64 * 1024;
+
+/// Add `chunk` of a running command's output to `live`, keeping it under
+/// `LIVE_LOG_LIMIT` by dropping whole lines from the front.
+fn append_live(live: &Mutex<String>, chunk: &[u8]) {
+    let Ok(mut live) = live.lock() else { return };
+    live.push_str(&String::from_utf8_lossy(chunk));
+    if live.len() > LIVE_LOG_LIMIT {
+        let excess = (live.len() - LIVE_LOG_LIMIT..).find(|&i| live.is_char_boundary(i));
+        let excess = excess.unwrap_or(live.len());
+        let cut = live[excess..].find('\n').map_or(live.len(), |i| excess + i + 1);
+        live.drain(..cut);
+    }
+}
+
+/// Wait for `child`, draining any piped output, and kill it if the global
+/// deadline passes, `timeout` runs out or Ctrl-C is pressed first.
+/// Stdout and stderr are each read on their own thread, so a child that
+/// fills one pipe while nothing reads the other cannot deadlock us. With
+/// `live`, the output is also appended there as it arrives.
+fn wait_for(
+    mut child: Child,
+    timeout: Option<Duration>,
+    live: Option<&Arc<Mutex<String>>>,
+) -> Result<Output> {
+    fn drain(
+        pipe: Option<impl Read + Send + 'static>,
+        live: Option<Arc<Mutex<String>>>,
+    ) -> Option<JoinHandle<Vec<u8>>> {
+        pipe.map(|mut pipe| {
+            thread::spawn(move || {
+                let mut buf = Vec::new();
+                let Some(live) = live else {
+                    let _ = pipe.read_to_end(&mut buf);
+                    return buf;
+                };
+                let mut chunk = [0u8; 8192];
+                loop {
+                    match pipe.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            buf.extend_from_slice(&chunk[..n]);
+                            append_live(&live, &chunk[..n]);
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                        Err(_) => break,
+                    }
+                }
+                buf
+            })
+        })
+    }
+
+    let stdout = drain(child.stdout.take(), live.cloned());
+    let stderr = drain(child.stderr.take(), live.cloned());
+    let collect = |handle: Option<JoinHandle<Vec<u8>>>| {
+        handle.map(|h| h.join().unwrap_or_default()).unwrap_or_default()
+    };
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Output {
+                status,
+                stdout: collect(stdout),
+                stderr: collect(stderr),
+            });
+        }
+        if deadline_reached() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(GlobalTimeout.into());
+        }
+        if let Some(limit) = timeout.filter(|limit| started.elapsed() >= *limit) {
+            terminate(&mut child);
+            return Err(CommandTimeout(limit).into());
+        }
+        if INTERRUPTED.swap(false, Ordering::SeqCst) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Interrupted.into());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Ask `child` to exit with SIGTERM, so programs like sudo can restore the
+/// terminal, and SIGKILL it if it is still there a second later.
+fn terminate(child: &mut Child) {
+    let _ = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status();
+    for _ in 0..20 {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Build `bash -c <cmd>`, wrapped in `sudo -u <user>` when `run_as` is set.
+fn shell(cmd: &str, run_as: Option<&str>) -> Command {
+    match run_as {
+        Some(user) => {
+            let mut command = Command::new("sudo");
+            command.args(["-u", user, "--", "bash", "-c", cmd]);
+            command
+        }
+        None => {
+            let mut command = Command::new("bash");
+            command.arg("-c").arg(cmd);
+            command
+        }
+    }
+}
 
 /// Run a command through `bash -c` and capture output.
+/// Stdin is `/dev/null`, so a command waiting for input fails fast instead of
+/// hanging the UI.
 pub fn run_command(cmd: &str) -> Result<Output> {
-    let output = Command::new("bash")
-        .arg("-c")
-        .arg(cmd)
-        .output()
+    run_command_as(cmd, None, None)
+}
+
+/// Like `run_command`, optionally as another user and with a time limit.
+pub fn run_command_as(
+    cmd: &str,
+    run_as: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    run_command_live(cmd, run_as, timeout, None)
+}
+
+/// Like `run_command_as`, also appending the output to `live` as it arrives,
+/// for the TUI to show while a worker runs the command.
+fn run_command_live(
+    cmd: &str,
+    run_as: Option<&str>,
+    timeout: Option<Duration>,
+    live: Option<&Arc<Mutex<String>>>,
+) -> Result<Output> {
+    let child = shell(cmd, run_as)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
         .with_context(|| format!("Failed to execute command: {}", cmd))?;
-    Ok(output)
+    wait_for(child, timeout, live)
+}
+
+/// Run a command through `bash -c` and stream output directly to the terminal.
+/// This is useful for long-running installs (e.g. apt-get) where we want
+/// to see progress in real time rather than only after completion.
+/// Stdin is only attached to the terminal when `interactive` is set.
+pub fn run_command_streaming(
+    cmd: &str,
+    interactive: bool,
+    run_as: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<ExitStatus> {
+    let stdin = if interactive {
+        Stdio::inherit()
+    } else {
+        Stdio::null()
+    };
+    let child = shell(cmd, run_as)
+        .stdin(stdin)
+        .spawn()
+        .with_context(|| format!("Failed to execute command: {}", cmd))?;
+    Ok(wait_for(child, timeout, None)?.status)
+}
+
+/// Strict check that every `*_file` script, and every template `source` and
+/// service `unit_file` without vars in its path, exists under `base_dir`.
+pub fn check_script_files(steps_file: &StepFile, base_dir: &Path) -> Result<(), ValidationErrors> {
+    let mut errors = Vec::new();
+    for (i, step) in steps_file.steps.iter().enumerate() {
+        for (field, _, file) in step.script_fields() {
+            let Some(file) = file else { continue };
+            let path = resolve_against(base_dir, file);
+            if !Path::new(&path).is_file() {
+                let message =
+                    format!("Step '{}' {}_file '{}' does not exist.", step.name, field, path);
+                errors.push(Issue { step: Some(i), message });
+            }
+        }
+        let (what, file) = match &step.kind {
+            StepKind::Template { params } => ("template source", Some(&params.source)),
+            StepKind::Service { params } => ("unit_file", params.unit_file.as_ref()),
+            _ => ("", None),
+        };
+        if let Some(file) = file.filter(|file| !file.contains("${")) {
+            let path = expand_home(&resolve_against(base_dir, file));
+            if !Path::new(&path).is_file() {
+                let message = format!("Step '{}' {} '{}' does not exist.", step.name, what, path);
+                errors.push(Issue { step: Some(i), message });
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
+
+/// Check every shell snippet in the file with `bash -n` (parse only, no
+/// execution), so syntax errors surface before anything runs. Script files
+/// are read relative to `base_dir`; missing ones are left to
+/// `check_script_files`.
+pub fn check_syntax(steps_file: &StepFile, base_dir: &Path) -> Result<()> {
+    if !on_path("bash") {
+        return Err(anyhow!(
+            "bash was not found on PATH; pass --no-syntax-check to skip the syntax check"
+        ));
+    }
+
+    for step in &steps_file.steps {
+        let mut snippets = Vec::new();
+        for (field, inline, file) in step.script_fields() {
+            let text = match (inline, file) {
+                (Some(text), _) => Some(text.to_string()),
+                (None, Some(file)) => std::fs::read_to_string(resolve_against(base_dir, file)).ok(),
+                (None, None) => None,
+            };
+            snippets.push((field, text));
+        }
+        if let StepKind::AppSelection { params } = &step.kind {
+            for app in &params.apps {
+                snippets.push(("install", params.install_command(app)));
+            }
+        }
+
+        for (field, snippet) in snippets {
+            let Some(snippet) = snippet else { continue };
+            let snippet = snippet.as_str();
+            let out = Command::new("bash")
+                .args(["-n", "-c", snippet])
+                .stdin(Stdio::null())
+                .output()
+                .context("Failed to run bash -n")?;
+            if !out.status.success() {
+                return Err(anyhow!(
+                    "Step '{}' {} has a syntax error:\n{}",
+                    step.name,
+                    field,
+                    String::from_utf8_lossy(&out.stderr).trim()
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Like `interpolate`, but names missing from `vars` are taken from the
+/// environment, and a name found in neither is an error instead of being
+/// left for bash. Only plain `${NAME}` references count; bash forms such as
+/// `${HOME:-/root}` are left alone.
+pub fn interpolate_defined(template: &str, vars: &Vars) -> Result<String> {
+    let text = interpolate(template, vars);
+    let mut undefined = Vec::new();
+    for name in var_references(&text) {
+        if std::env::var_os(name).is_none() && !undefined.contains(&name) {
+            undefined.push(name);
+        }
+    }
+    if !undefined.is_empty() {
+        let names: Vec<String> = undefined.iter().map(|name| format!("${{{}}}", name)).collect();
+        return Err(anyhow!(
+            "undefined variable(s) {}; set them in `vars`, `env_file` or the environment",
+            names.join(", ")
+        ));
+    }
+    Ok(text)
+}
+
+/// Names of the plain `${NAME}` references in `text`, in order, repeats
+/// included. Bash forms such as `${HOME:-/root}` or `${#list[@]}` are not
+/// references to a var.
+fn var_references(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else { break };
+        let name = &after[..end];
+        let plain = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if plain {
+            names.push(name);
+        }
+        rest = &after[end + 1..];
+    }
+    names
+}
+
+/// Strict check that every `${NAME}` in the steps' commands and params can
+/// be resolved: by `vars` (the file's `vars` plus the env file), a var some
+/// step sets (`prompt` fields, `capture`), or the environment. A name the
+/// script assigns itself (`NAME=`, `for NAME in`, `read NAME`) also counts.
+/// Unreadable script files are left to `check_script_files`.
+pub fn check_vars(
+    steps_file: &StepFile,
+    vars: &Vars,
+    base_dir: &Path,
+) -> Result<(), ValidationErrors> {
+    let mut known: HashSet<&str> = vars.keys().map(String::as_str).collect();
+    for step in &steps_file.steps {
+        known.extend(step.capture.as_deref());
+        if let StepKind::Prompt { params } = &step.kind {
+            known.extend(params.fields.iter().map(|field| field.var.as_str()));
+        }
+    }
+
+    let mut errors = Vec::new();
+    for (i, step) in steps_file.steps.iter().enumerate() {
+        // (where, text, whether it is a shell script that may set its own vars)
+        let mut fields: Vec<(String, String, bool)> = Vec::new();
+        for (field, inline, file) in step.script_fields() {
+            let text = match (inline, file) {
+                (Some(text), _) => text.to_string(),
+                (None, Some(file)) => {
+                    match std::fs::read_to_string(resolve_against(base_dir, file)) {
+                        Ok(text) => text,
+                        Err(_) => continue,
+                    }
+                }
+                (None, None) => continue,
+            };
+            fields.push((field.to_string(), text, true));
+        }
+        let mut param = |name: &str, text: &str| {
+            fields.push((format!("params.{}", name), text.to_string(), false));
+        };
+        match &step.kind {
+            StepKind::AddText { params } => {
+                param("file", &params.file);
+                param("content", &params.content);
+            }
+            StepKind::ReplaceInFile { params } => {
+                param("file", &params.file);
+                param("find", &params.find);
+                param("replace", &params.replace);
+            }
+            StepKind::LineInFile { params } => {
+                param("file", &params.file);
+                if let Some(line) = &params.line {
+                    param("line", line);
+                }
+                if let Some(regex) = &params.regex {
+                    param("regex", regex);
+                }
+            }
+            StepKind::GitClone { params } => {
+                param("url", &params.url);
+                param("dest", &params.dest);
+            }
+            StepKind::Service { params } => {
+                param("name", &params.name);
+                if let Some(file) = &params.unit_file {
+                    param("unit_file", file);
+                }
+            }
+            StepKind::Package { params } => {
+                let lists = std::iter::once(&params.packages).chain(params.overrides.values());
+                for name in lists.flatten() {
+                    param("packages", name);
+                }
+            }
+            StepKind::Download { params } => {
+                param("url", &params.url);
+                param("dest", &params.dest);
+            }
+            StepKind::Template { params } => {
+                param("source", &params.source);
+                param("dest", &params.dest);
+                for (name, value) in &params.vars {
+                    param(&format!("vars.{}", name), value);
+                }
+            }
+            StepKind::Symlink { params } => {
+                for (n, link) in params.links.iter().enumerate() {
+                    param(&format!("links[{}].source", n), &link.source);
+                    param(&format!("links[{}].target", n), &link.target);
+                }
+            }
+            StepKind::AppSelection { params } => {
+                for app in &params.apps {
+                    if let Some(install) = params.install_command(app) {
+                        param(&format!("apps[{}].install", app.name), &install);
+                    }
+                }
+            }
+            StepKind::Script | StepKind::GitConfig { .. } | StepKind::Prompt { .. } => {}
+        }
+
+        for (field, text, script) in &fields {
+            let mut reported = Vec::new();
+            for name in var_references(text) {
+                let defined = known.contains(name)
+                    || std::env::var_os(name).is_some()
+                    || (*script && assigns_var(text, name));
+                if !defined && !reported.contains(&name) {
+                    reported.push(name);
+                    errors.push(Issue {
+                        step: Some(i),
+                        message: format!(
+                            "Step '{}' {} uses undefined variable ${{{}}}.",
+                            step.name, field, name
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
+
+/// Whether `script` gives `name` a value itself, so `${name}` is a shell var.
+fn assigns_var(script: &str, name: &str) -> bool {
+    let name = regex::escape(name);
+    let pattern = format!(
+        r"(^|[\s;&|(]){name}=|\bfor\s+{name}\s|\bread\b[^\n;]*\s{name}\b|\blocal\s+{name}\b",
+        name = name
+    );
+    regex::Regex::new(&pattern).is_ok_and(|re| re.is_match(script))
+}
+
+/// Replace `${var}` references with values from `vars`.
+/// Unknown names are left untouched so bash can still expand environment variables.
+pub fn interpolate(template: &str, vars: &Vars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match vars.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[start..start + 2 + end + 1]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Longest line kept in step logs, in chars (`--max-line-len`); 0 keeps everything.
+static MAX_LINE_LEN: OnceCell<usize> = OnceCell::new();
+
+const DEFAULT_MAX_LINE_LEN: usize = 4000;
+
+const TRUNCATED: &str = "…[truncated]";
+
+pub fn set_max_line_len(max: usize) {
+    let _ = MAX_LINE_LEN.set(max);
+}
+
+/// Cut lines longer than the `--max-line-len` cap (counted in chars), so
+/// huge single-line output (progress bars without newlines) stays cheap to
+/// render.
+fn truncate_lines(text: &str) -> Cow<'_, str> {
+    let max = MAX_LINE_LEN.get().copied().unwrap_or(DEFAULT_MAX_LINE_LEN);
+    // A line of at most `max` bytes has at most `max` chars; only longer
+    // ones need counting.
+    let fits = |line: &str| line.len() <= max || line.chars().count() <= max;
+    if max == 0 || text.lines().all(fits) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len().min(max * 64));
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        match body.char_indices().nth(max) {
+            Some((cut, _)) => {
+                out.push_str(&body[..cut]);
+                out.push_str(TRUNCATED);
+                if line.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            None => out.push_str(line),
+        }
+    }
+    Cow::Owned(out)
+}
+
+const DROPPED: &str = "…[earlier output dropped]\n";
+
+/// Keep only the last `max` lines of `log` (`--max-log-lines`), with a
+/// marker line on top. Returns whether anything was dropped.
+pub fn drop_old_lines(log: &mut String, max: usize) -> bool {
+    let body = log.strip_prefix(DROPPED).unwrap_or(log);
+    let lines = body.lines().count();
+    if lines <= max {
+        return false;
+    }
+    let keep_from = body
+        .split_inclusive('\n')
+        .take(lines - max)
+        .map(str::len)
+        .sum::<usize>();
+    *log = format!("{}{}", DROPPED, &body[keep_from..]);
+    true
+}
+
+/// Untruncated copy of every command's output (`--full-log`).
+static FULL_LOG: OnceCell<Mutex<File>> = OnceCell::new();
+
+pub fn open_full_log(path: &str) -> Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open full log: {}", path))?;
+    let _ = FULL_LOG.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Append `records` (already redacted) to the `--full-log` file, if any.
+fn write_full_log(step: &str, records: &[CommandRecord]) {
+    let Some(file) = FULL_LOG.get() else { return };
+    let Ok(mut file) = file.lock() else { return };
+    for record in records {
+        // Best effort: a full disk should not fail the step.
+        let _ = write!(
+            file,
+            "[{}] $ {}\n{}{}[exit code: {}]\n\n",
+            step,
+            record.command,
+            record.stdout,
+            if record.stderr.is_empty() {
+                String::new()
+            } else {
+                format!("[stderr]\n{}", record.stderr)
+            },
+            record.exit_code
+        );
+    }
+    let _ = file.flush();
+}
+
+/// Invalid UTF-8 sequences in the output become U+FFFD; the rest is kept.
+fn append_output(log: &mut String, label: &str, out: &Output) {
+    let status_code = out.status.code().unwrap_or(-1);
+    log.push_str(&format!("\n$ {}\n", label));
+    if !out.stdout.is_empty() {
+        log.push_str(&truncate_lines(&String::from_utf8_lossy(&out.stdout)));
+    }
+    if !out.stderr.is_empty() {
+        log.push_str("\n[stderr]\n");
+        log.push_str(&truncate_lines(&String::from_utf8_lossy(&out.stderr)));
+    }
+    log.push_str(&format!("\n[exit code: {}]\n", status_code));
+}
+
+/// Append captured output to the step log and keep a structured record of it.
+fn record_output(runtime: &mut StepRuntime, label: &str, out: &Output) {
+    append_output(&mut runtime.log, label, out);
+    runtime.commands.push(CommandRecord {
+        command: label.to_string(),
+        exit_code: out.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&out.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+    });
+}
+
+/// Record a command whose output went straight to the terminal.
+fn record_status(runtime: &mut StepRuntime, label: &str, status: &ExitStatus) {
+    let exit_code = status.code().unwrap_or(-1);
+    runtime.log.push_str(&format!("\n[exit code: {}]\n", exit_code));
+    runtime.commands.push(CommandRecord {
+        command: label.to_string(),
+        exit_code,
+        stdout: String::new(),
+        stderr: String::new(),
+    });
+}
+
+/// Run a step command, either captured into the log or, for interactive
+/// steps, attached to the real terminal. Returns whether it succeeded.
+fn run_logged(cmd: &str, step: &Step, runtime: &mut StepRuntime) -> Result<bool> {
+    if dry_run() {
+        log_dry_run(&mut runtime.log, cmd);
+        return Ok(true);
+    }
+    run_predicate(cmd, step, runtime)
+}
+
+/// Like `run_logged`, but also runs under `--dry-run`: for commands that only
+/// decide whether a step goes ahead (`pre_script`), so a dry run skips the
+/// same steps a real run would.
+fn run_predicate(cmd: &str, step: &Step, runtime: &mut StepRuntime) -> Result<bool> {
+    if dry_run() {
+        runtime.log.push_str("(dry run; pre_script runs for real to decide on the step)\n");
+    }
+    runtime.set_current_command(Some(cmd));
+    let run_as = step.run_as.as_deref();
+    if let Some(user) = run_as {
+        runtime.log.push_str(&format!("\n[running as user '{}']", user));
+    }
+    let timeout = step_timeout(step);
+    log_verbose(&mut runtime.log, || {
+        format!(
+            "shell: {}, stdin: {}, timeout: {}",
+            shell_description(run_as),
+            if step.interactive { "terminal" } else { "closed" },
+            timeout.map_or("none".to_string(), |t| format!("{}s", t.as_secs()))
+        )
+    });
+    if step.interactive {
+        let status = match run_command_streaming(cmd, true, run_as, timeout) {
+            Err(e) if e.is::<CommandTimeout>() => return Ok(record_timeout(runtime, cmd, &e)),
+            res => res?,
+        };
+        runtime
+            .log
+            .push_str(&format!("\n$ {}\n(interactive; output went to the terminal)\n", cmd));
+        record_status(runtime, cmd, &status);
+        Ok(status.success())
+    } else {
+        let out = match run_command_live(cmd, run_as, timeout, Some(&runtime.live_log)) {
+            Err(e) if e.is::<CommandTimeout>() => return Ok(record_timeout(runtime, cmd, &e)),
+            res => res?,
+        };
+        record_output(runtime, cmd, &out);
+        Ok(out.status.success())
+    }
+}
+
+/// Log and record a command killed by its step timeout, as a failure.
+fn record_timeout(runtime: &mut StepRuntime, cmd: &str, error: &anyhow::Error) -> bool {
+    runtime.log.push_str(&format!("\n$ {}\n[{}; killed]\n", cmd, error));
+    runtime.commands.push(CommandRecord {
+        command: cmd.to_string(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: error.to_string(),
+    });
+    false
+}
+
+/// Judge the `script` command recorded at `runtime.commands[record]` by the
+/// step's `expect_exit` and `success_if`, logging what decided. Without
+/// either, or when nothing ran (dry run), `exit_ok` stands.
+fn script_succeeded(step: &Step, exit_ok: bool, record: usize, runtime: &mut StepRuntime) -> bool {
+    if step.expect_exit.is_none() && step.success_if.is_none() {
+        return exit_ok;
+    }
+    let Some(record) = runtime.commands.get(record) else {
+        return exit_ok;
+    };
+
+    let expected = step.expect_exit.unwrap_or(0);
+    let mut succeeded = record.exit_code == expected;
+    let mut notes = vec![format!(
+        "exit code {} {} {}",
+        record.exit_code,
+        if succeeded { "matches" } else { "is not" },
+        match step.expect_exit {
+            Some(expected) => format!("expect_exit {}", expected),
+            None => "0".to_string(),
+        },
+    )];
+    if let Some(pattern) = &step.success_if {
+        let matched = regex::Regex::new(pattern)
+            .is_ok_and(|re| re.is_match(&record.stdout) || re.is_match(&record.stderr));
+        notes.push(format!(
+            "output {} success_if '{}'",
+            if matched { "matches" } else { "does not match" },
+            pattern
+        ));
+        succeeded &= matched;
+    }
+    runtime.log.push_str(&format!(
+        "\n[{}] {}\n",
+        if succeeded { "success" } else { "failure" },
+        notes.join("; ")
+    ));
+    succeeded
+}
+
+/// With `continue_on_error`, note the failure in the log and let the step go on.
+/// Returns whether the failure was tolerated.
+fn tolerate_failure(step: &Step, phase: &str, runtime: &mut StepRuntime) -> bool {
+    if step.continue_on_error {
+        runtime.log.push_str(&format!(
+            "\n[warning] {} failed; tolerated because of continue_on_error.\n",
+            phase
+        ));
+    }
+    step.continue_on_error
+}
+
+/// Store the trimmed stdout of the last command into `var`.
+/// Nothing is captured when the command failed or ran interactively.
+fn capture_stdout(var: &str, captured: bool, runtime: &mut StepRuntime, ctx: &mut RunContext) {
+    let value = match runtime.commands.last() {
+        Some(record) if captured => record.stdout.trim().to_string(),
+        _ => {
+            runtime
+                .log
+                .push_str(&format!("\nNothing captured into ${{{}}}.\n", var));
+            return;
+        }
+    };
+    runtime
+        .log
+        .push_str(&format!("\nCaptured stdout into ${{{}}}.\n", var));
+    ctx.vars.insert(var.to_string(), value);
+}
+
+/// How long `sudo -v` may take at startup unless `--sudo-timeout` says otherwise.
+pub const DEFAULT_SUDO_TIMEOUT: u64 = 60;
+
+/// Start sudo session at startup. If `sudo -v` is still waiting after
+/// `timeout_secs` (0 = wait forever) or is cancelled with Ctrl-C, it is
+/// stopped and the run goes on without a sudo session, with a warning in `log`.
+pub fn start_sudo_session(log: &mut String, timeout_secs: Option<u64>) -> Result<()> {
+    if dry_run() {
+        log.push_str("Dry run: not starting a sudo session.\n");
+        return Ok(());
+    }
+    log.push_str("Initializing sudo session with `sudo -v`...\n");
+    let timeout = match timeout_secs.unwrap_or(DEFAULT_SUDO_TIMEOUT) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let child = shell("sudo -v", None)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute command: sudo -v")?;
+    let output = wait_for(child, timeout, None);
+    // sudo usually exits on Ctrl-C itself, before `wait_for` notices it.
+    let cancelled = INTERRUPTED.swap(false, Ordering::SeqCst);
+    let output = match output {
+        Err(e) if e.is::<CommandTimeout>() || e.is::<Interrupted>() => Err(e.to_string()),
+        Ok(output) if cancelled && !output.status.success() => Err(Interrupted.to_string()),
+        res => Ok(res?),
+    };
+    let output = match output {
+        Ok(output) => output,
+        Err(reason) => {
+            log.push_str(&format!(
+                "[warning] sudo -v {}; continuing without a sudo session. \
+                 Commands using sudo will ask for the password themselves.\n",
+                reason
+            ));
+            return Ok(());
+        }
+    };
+    append_output(log, "sudo -v", &output);
+    if !output.status.success() {
+        return Err(anyhow!("sudo -v failed; sudo may not be available"));
+    }
+    Ok(())
+}
+
+/// Run a single step (pre/script/post + task-specific logic).  
+/// Returns updated StepRuntime. Secret values are redacted from the step log.
+/// Errors carry the step name as context.
+pub fn run_step(step: &Step, runtime: &mut StepRuntime, ctx: &mut RunContext) -> Result<()> {
+    if step.run_once && already_run_once(step, runtime, ctx) {
+        return Ok(());
+    }
+    let before = runtime.commands.len();
+    runtime.max_attempts = step.retries + 1;
+    let mut attempt = 1;
+    let res = loop {
+        runtime.attempts.store(attempt, Ordering::SeqCst);
+        log_verbose(&mut runtime.log, || {
+            format!("attempt {}/{} (retries: {})", attempt, runtime.max_attempts, step.retries)
+        });
+        let res = execute_step(step, runtime, ctx)
+            .with_context(|| format!("step '{}' ({})", step.name, step.kind.label()));
+        let stop = match &res {
+            Ok(()) => runtime.status != StepStatus::Failed,
+            // Running out of time or Ctrl-C is not something to retry.
+            Err(e) => e.is::<GlobalTimeout>() || e.is::<Interrupted>(),
+        };
+        if stop || attempt >= runtime.max_attempts {
+            break res;
+        }
+        if let Err(e) = &res {
+            runtime.log.push_str(&format!("\n[ERROR] {:#}\n", e));
+        }
+        runtime.log.push_str(&format!(
+            "\n[retry] Attempt {}/{} failed; trying again.\n\n",
+            attempt, runtime.max_attempts
+        ));
+        attempt += 1;
+    };
+    runtime.set_current_command(None);
+    ctx.redact_runtime(runtime);
+    write_full_log(&step.name, &runtime.commands[before..]);
+    if step.run_once && runtime.status == StepStatus::Success && !dry_run() {
+        if let Some(once) = &ctx.once {
+            if let Err(e) = once.mark(&step.name) {
+                runtime.log.push_str(&format!("\n[run_once] {:#}; it will run again.\n", e));
+            }
+        }
+    }
+    res
+}
+
+/// `run_once`: skip the step when a marker says it already succeeded in an
+/// earlier run. Returns whether it was skipped.
+fn already_run_once(step: &Step, runtime: &mut StepRuntime, ctx: &RunContext) -> bool {
+    let Some(once) = &ctx.once else {
+        return false;
+    };
+    match once.contains(&step.name) {
+        Ok(false) => false,
+        Ok(true) => {
+            runtime.status = StepStatus::Skipped;
+            runtime.log.push_str(&format!(
+                "== {} ==\nAlready run once (run_once); skipped. Use --reset-once to run it \
+                 again.\n",
+                step.name
+            ));
+            true
+        }
+        Err(e) => {
+            runtime.log.push_str(&format!("[run_once] {:#}; running the step.\n", e));
+            false
+        }
+    }
+}
+
+fn execute_step(step: &Step, runtime: &mut StepRuntime, ctx: &mut RunContext) -> Result<()> {
+    runtime.status = StepStatus::Running;
+    runtime.set_by_hand = false;
+    runtime.log.push_str(&format!("== Running step: {} ==\n", step.name));
+    log_verbose(&mut runtime.log, || {
+        format!("type: {}, vars: {}", step.kind.tag(), var_names(&ctx.vars))
+    });
+    if !step.interactive {
+        runtime
+            .log
+            .push_str("(stdin closed; set `interactive: true` to attach the terminal)\n");
+    }
+
+    let [(_, pre, pre_file), (_, script, script_file), (_, post, post_file)] =
+        step.script_fields();
+
+    // Run pre_script if any.
+    if let Some(pre) = ctx.script_text(pre, pre_file)? {
+        runtime.log.push_str("\n--- pre_script ---\n");
+        if !run_predicate(&pre, step, runtime)?
+            && !tolerate_failure(step, "pre_script", runtime)
+        {
+            if step.pre_script_is_gate {
+                runtime.log.push_str(
+                    "\n[gate] pre_script failed, so the step is skipped on purpose; this is \
+                     not an error (set `pre_script_is_gate: false` to fail the step instead).\n",
+                );
+                runtime.status = StepStatus::Skipped;
+            } else {
+                runtime
+                    .log
+                    .push_str("\npre_script failed; step failed (pre_script_is_gate: false).\n");
+                runtime.status = StepStatus::Failed;
+            }
+            return Ok(());
+        }
+    }
+
+    // Dispatch main task depending on type.
+    match &step.kind {
+        StepKind::Script => {
+            if let Some(script) = ctx.script_text(script, script_file)? {
+                runtime.log.push_str("\n--- script ---\n");
+                let before = runtime.commands.len();
+                let exit_ok = run_logged(&script, step, runtime)?;
+                let succeeded = script_succeeded(step, exit_ok, before, runtime);
+                if !succeeded && !tolerate_failure(step, "script", runtime) {
+                    runtime.status = StepStatus::Failed;
+                    return Ok(());
+                }
+                if let Some(var) = &step.capture {
+                    capture_stdout(var, succeeded && !step.interactive, runtime, ctx);
+                }
+            } else {
+                runtime.log.push_str("\nNo script specified for script step.\n");
+            }
+        }
+        StepKind::AddText { params } => {
+            let mut params = params.clone();
+            params.file = ctx.resolve_path(&interpolate(&params.file, &ctx.vars));
+            params.content = interpolate(&params.content, &ctx.vars);
+            runtime.log
+                .push_str(&format!("\n--- add_text to {} ---\n", params.file));
+            run_add_text(&params, &mut runtime.log)?;
+        }
+        StepKind::ReplaceInFile { params } => {
+            let mut params = params.clone();
+            params.file = ctx.resolve_path(&interpolate(&params.file, &ctx.vars));
+            params.find = interpolate(&params.find, &ctx.vars);
+            params.replace = interpolate(&params.replace, &ctx.vars);
+            runtime.log
+                .push_str(&format!("\n--- replace_in_file in {} ---\n", params.file));
+            if !run_replace_in_file(&params, step, runtime)?
+                && !tolerate_failure(step, "replace_in_file", runtime)
+            {
+                runtime.status = StepStatus::Failed;
+                return Ok(());
+            }
+        }
+        StepKind::LineInFile { params } => {
+            let params = line_in_file_params(params, ctx);
+            runtime.log
+                .push_str(&format!("\n--- line_in_file in {} ---\n", params.file));
+            run_line_in_file(&params, &mut runtime.log)?;
+        }
+        StepKind::GitClone { params } => {
+            let mut params = params.clone();
+            params.url = interpolate(&params.url, &ctx.vars);
+            params.dest = expand_home(&ctx.resolve_path(&interpolate(&params.dest, &ctx.vars)));
+            runtime.log
+                .push_str(&format!("\n--- git_clone into {} ---\n", params.dest));
+            if !run_git_clone(&params, step, runtime)?
+                && !tolerate_failure(step, "git_clone", runtime)
+            {
+                runtime.status = StepStatus::Failed;
+                return Ok(());
+            }
+        }
+        StepKind::Service { params } => {
+            let name = interpolate(&params.name, &ctx.vars);
+            runtime.log.push_str(&format!("\n--- service {} ---\n", name));
+            for cmd in service_commands(params, &name, ctx) {
+                if !run_logged(&cmd, step, runtime)?
+                    && !tolerate_failure(step, "service", runtime)
+                {
+                    runtime.status = StepStatus::Failed;
+                    return Ok(());
+                }
+            }
+            if params.status {
+                let cmd = format!("{} status --no-pager {}", systemctl(params), shell_quote(&name));
+                if !run_logged(&cmd, step, runtime)? {
+                    runtime.log.push_str("(the status is only logged; the step goes on)\n");
+                }
+            }
+        }
+        StepKind::Package { params } => {
+            let manager = package_manager(params)?;
+            runtime.log.push_str(&format!("\n--- package ({}) ---\n", manager.label()));
+            for cmd in package_commands(params, manager, &ctx.vars) {
+                if !run_logged(&cmd, step, runtime)?
+                    && !tolerate_failure(step, "package", runtime)
+                {
+                    runtime.status = StepStatus::Failed;
+                    return Ok(());
+                }
+            }
+        }
+        StepKind::Download { params } => {
+            let url = interpolate(&params.url, &ctx.vars);
+            let dest = ctx.resolve_path(&interpolate(&params.dest, &ctx.vars));
+            let dest = PathBuf::from(expand_home(&dest));
+            runtime.log.push_str(&format!("\n--- download {} ---\n", url));
+            if !run_download(params, &url, &dest, step, runtime)?
+                && !tolerate_failure(step, "download", runtime)
+            {
+                runtime.status = StepStatus::Failed;
+                return Ok(());
+            }
+        }
+        StepKind::Template { params } => {
+            let (source, dest) = template_paths(params, ctx);
+            runtime.log
+                .push_str(&format!("\n--- template to {} ---\n", dest.display()));
+            run_template(params, &source, &dest, ctx, &mut runtime.log)?;
+        }
+        StepKind::Symlink { params } => {
+            runtime.log.push_str("\n--- symlink ---\n");
+            let links = symlink_paths(params, ctx);
+            run_symlink(&links, params, &mut runtime.log)?;
+        }
+        StepKind::GitConfig { params: _ } => {
+            // For git_config, the interactive UI (ratatui) is responsible for
+            // gathering values and invoking the actual configuration logic.
+            runtime.log.push_str("\n--- git_config (handled by TUI) ---\n");
+        }
+        StepKind::AppSelection { params: _ } => {
+            // For app_selection, the interactive UI (ratatui) is responsible for
+            // gathering the selection and invoking the actual installation logic.
+            runtime.log.push_str("\n--- app_selection (handled by TUI) ---\n");
+        }
+        StepKind::Prompt { params: _ } => {
+            // For prompt, the interactive UI (ratatui) collects the values and
+            // stores them into the global vars map.
+            runtime.log.push_str("\n--- prompt (handled by TUI) ---\n");
+        }
+    }
+
+    // Run post_script if any.
+    if let Some(post) = ctx.script_text(post, post_file)? {
+        runtime.log.push_str("\n--- post_script ---\n");
+        if !run_logged(&post, step, runtime)?
+            && !tolerate_failure(step, "post_script", runtime)
+        {
+            runtime.status = StepStatus::Failed;
+            return Ok(());
+        }
+    }
+
+    if runtime.status == StepStatus::Running {
+        runtime.status = StepStatus::Success;
+    }
+
+    Ok(())
+}
+
+fn systemctl(params: &ServiceParams) -> &'static str {
+    if params.user {
+        "systemctl --user"
+    } else {
+        "sudo systemctl"
+    }
+}
+
+/// The commands of a service step for unit `name`: installing its
+/// `unit_file` (and reloading systemd) when that differs from the installed
+/// copy, then the `systemctl` action.
+fn service_commands(params: &ServiceParams, name: &str, ctx: &RunContext) -> Vec<String> {
+    let mut cmds = Vec::new();
+    if let Some(file) = &params.unit_file {
+        let source = expand_home(&ctx.resolve_path(&interpolate(file, &ctx.vars)));
+        let file_name = if name.contains('.') {
+            name.to_string()
+        } else {
+            format!("{}.service", name)
+        };
+        let (dir, install) = if params.user {
+            (expand_home("~/.config/systemd/user"), "install")
+        } else {
+            ("/etc/systemd/system".to_string(), "sudo install")
+        };
+        let (source, dest) = (shell_quote(&source), shell_quote(&format!("{}/{}", dir, file_name)));
+        let copy = format!("{} -D -m 644 {} {}", install, source, dest);
+        cmds.push(format!(
+            "cmp -s {} {} || {{ {} && {} daemon-reload; }}",
+            source,
+            dest,
+            copy,
+            systemctl(params)
+        ));
+    }
+    cmds.push(format!("{} {} {}", systemctl(params), params.action.args(), shell_quote(name)));
+    cmds
+}
+
+/// What running `step` now would do, for the TUI's `e` overlay: how its
+/// commands run, its options, and its commands with vars substituted
+/// (secrets redacted). Nothing is executed, and script files are only read.
+pub fn explain_step(step: &Step, ctx: &RunContext) -> String {
+    let mut out = String::new();
+    let mut line = |text: String| {
+        out.push_str(&text);
+        out.push('\n');
+    };
+    let yes_no = |on: bool| if on { "yes" } else { "no" };
+    line(format!("Type: {}", step.kind.label()));
+    line(format!(
+        "Shell: {} (stdin {})",
+        shell_description(step.run_as.as_deref()),
+        if step.interactive { "attached to the terminal" } else { "closed" }
+    ));
+    let cwd = std::env::current_dir().map(|dir| dir.display().to_string());
+    line(format!("Working directory: {}", cwd.unwrap_or_else(|e| e.to_string())));
+    line(format!("Relative paths in params resolve against: {}", ctx.base_dir.display()));
+    line(format!("Environment: inherited; vars for ${{...}}: {}", var_names(&ctx.vars)));
+    let timeout = step_timeout(step);
+    line(format!(
+        "Timeout per command: {}",
+        timeout.map_or("none".to_string(), |t| format!("{}s", t.as_secs()))
+    ));
+    line(format!("Retries: {}", step.retries));
+    line(format!("continue_on_error: {}", yes_no(step.continue_on_error)));
+    line(format!("optional: {}", yes_no(step.optional)));
+    if !step.depends_on.is_empty() {
+        line(format!("Waits for: {}", step.depends_on.join(", ")));
+    }
+    if dry_run() {
+        line("--dry-run: commands would be logged, not run (pre_script still runs)".to_string());
+    }
+
+    let [(_, pre, pre_file), (_, script, script_file), (_, post, post_file)] =
+        step.script_fields();
+    let mut section = |title: &str, text: Result<Option<String>>| {
+        let text = match text {
+            Ok(None) => return,
+            Ok(Some(text)) => text,
+            Err(e) => format!("(cannot read: {:#})", e),
+        };
+        out.push_str(&format!("\n--- {} ---\n{}\n", title, ctx.redact(text.trim_end())));
+    };
+    let gate = if step.pre_script_is_gate {
+        "pre_script (if it fails, the step is skipped)"
+    } else {
+        "pre_script (if it fails, the step fails)"
+    };
+    section(gate, ctx.script_text(pre, pre_file));
+    let vars = &ctx.vars;
+    let main = match &step.kind {
+        StepKind::Script => ctx.script_text(script, script_file),
+        StepKind::AddText { params } => Ok(Some(format!(
+            "Add to {}:\n{}",
+            ctx.resolve_path(&interpolate(&params.file, vars)),
+            interpolate(&params.content, vars)
+        ))),
+        StepKind::ReplaceInFile { params } => Ok(Some(format!(
+            "In {}, replace {}{:?} with {:?}{}{}",
+            ctx.resolve_path(&interpolate(&params.file, vars)),
+            if params.regex { "matches of the regex " } else { "" },
+            interpolate(&params.find, vars),
+            interpolate(&params.replace, vars),
+            params.count.map_or(String::new(), |count| format!(" (at most {} times)", count)),
+            match (params.sudo, &step.run_as) {
+                (true, _) => "\nThe file is written with `sudo tee`.",
+                (false, Some(_)) => "\nThe file is written with `tee` as the run_as user.",
+                (false, None) => "",
+            }
+        ))),
+        StepKind::LineInFile { params } => {
+            let params = line_in_file_params(params, ctx);
+            let matching = match (&params.regex, &params.line) {
+                (Some(regex), _) => format!("lines matching the regex {:?}", regex),
+                (None, Some(line)) => format!("lines equal to {:?}", line),
+                (None, None) => String::new(),
+            };
+            Ok(Some(match (params.state, &params.line) {
+                (LineState::Present, Some(line)) => format!(
+                    "Make sure {} contains {:?}{}",
+                    params.file,
+                    line,
+                    match &params.regex {
+                        Some(_) => format!(", replacing the last of the {}", matching),
+                        None => String::new(),
+                    }
+                ),
+                _ => format!("Remove the {} from {}", matching, params.file),
+            }))
+        }
+        StepKind::GitClone { params } => Ok(Some(format!(
+            "Clone {} into {}{}",
+            interpolate(&params.url, vars),
+            expand_home(&ctx.resolve_path(&interpolate(&params.dest, vars))),
+            if params.update { " (pull if it is already there)" } else { "" }
+        ))),
+        StepKind::Service { params } => {
+            let name = interpolate(&params.name, vars);
+            let mut cmds = service_commands(params, &name, ctx);
+            if params.status {
+                cmds.push(format!(
+                    "{} status --no-pager {}  (logged only)",
+                    systemctl(params),
+                    shell_quote(&name)
+                ));
+            }
+            Ok(Some(cmds.join("\n")))
+        }
+        StepKind::Package { params } => package_manager(params).map(|manager| {
+            let how = if params.manager.is_some() { "set by the step" } else { "detected" };
+            let mut text = format!("Package manager: {} ({})", manager.label(), how);
+            for cmd in package_commands(params, manager, vars) {
+                text.push_str(&format!("\n{}", cmd));
+            }
+            Some(text)
+        }),
+        StepKind::Download { params } => Ok(Some(format!(
+            "Download {} to {} with mode {}{}\n{}",
+            interpolate(&params.url, vars),
+            expand_home(&ctx.resolve_path(&interpolate(&params.dest, vars))),
+            params.mode(),
+            if params.force { " (replacing what is there)" } else { "" },
+            match &params.sha256 {
+                Some(hex) => format!("then check its sha256 is {}", hex.trim()),
+                None => "without checking it (no sha256)".to_string(),
+            }
+        ))),
+        StepKind::Template { params } => {
+            let (source, dest) = template_paths(params, ctx);
+            let mut names: Vec<&str> = params.vars.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            Ok(Some(format!(
+                "Render {} into {}{}{}",
+                source.display(),
+                dest.display(),
+                if names.is_empty() {
+                    String::new()
+                } else {
+                    format!(" with extra vars {}", names.join(", "))
+                },
+                if params.force { " (overwriting it if it differs)" } else { "" }
+            )))
+        }
+        StepKind::Symlink { params } => {
+            let mut text = match (params.backup, params.force) {
+                (true, _) => "Link (moving anything in the way to <target>.bak):",
+                (false, true) => "Link (replacing files or symlinks in the way):",
+                (false, false) => "Link (anything already in the way is a conflict):",
+            }
+            .to_string();
+            for (source, target) in symlink_paths(params, ctx) {
+                text.push_str(&format!("\n  {} -> {}", target.display(), source.display()));
+            }
+            Ok(Some(text))
+        }
+        StepKind::GitConfig { params } => Ok(Some(format!(
+            "Asks for your git user.name, user.email and core.editor (default {}), \
+             then sets them with git config --global",
+            params.default_editor
+        ))),
+        StepKind::AppSelection { params } => {
+            let mut text = "Asks which apps to install; their install commands:".to_string();
+            for app in &params.apps {
+                let install = params.install_command(app).unwrap_or_default();
+                text.push_str(&format!("\n  {}: {}", app.name, interpolate(&install, vars)));
+            }
+            Ok(Some(text))
+        }
+        StepKind::Prompt { params } => {
+            let fields: Vec<String> = params
+                .fields
+                .iter()
+                .map(|field| format!("  {} -> ${{{}}}", field.label, field.var))
+                .collect();
+            Ok(Some(format!("Asks for:\n{}", fields.join("\n"))))
+        }
+    };
+    section(step.kind.label(), main);
+    section("post_script", ctx.script_text(post, post_file));
+    out
+}
+
+/// Task: clone a repository, or pull / leave alone an existing checkout.
+/// Returns whether the git command succeeded.
+fn run_git_clone(params: &GitCloneParams, step: &Step, runtime: &mut StepRuntime) -> Result<bool> {
+    let dest = Path::new(&params.dest);
+    if dest.exists() {
+        if !dest.join(".git").exists() {
+            return Err(anyhow!("{} already exists and is not a git checkout", params.dest));
+        }
+        if !params.update {
+            runtime
+                .log
+                .push_str("Already cloned; leaving it alone (set `update: true` to pull).\n");
+            return Ok(true);
+        }
+        let cmd = format!("git -C {} pull --ff-only", shell_quote(&params.dest));
+        return run_logged(&cmd, step, runtime);
+    }
+
+    let mut cmd = "git clone".to_string();
+    if let Some(branch) = &params.branch {
+        cmd.push_str(&format!(" --branch {}", shell_quote(branch)));
+    }
+    if let Some(depth) = params.depth {
+        cmd.push_str(&format!(" --depth {}", depth));
+    }
+    cmd.push_str(&format!(" {} {}", shell_quote(&params.url), shell_quote(&params.dest)));
+    run_logged(&cmd, step, runtime)
+}
+
+/// The manager a package step uses: its `manager`, or the detected one.
+fn package_manager(params: &PackageParams) -> Result<PackageManager> {
+    params.manager.or_else(detect_package_manager).ok_or_else(|| {
+        anyhow!(
+            "No supported package manager found (apt, dnf, pacman, zypper, brew); \
+             set `manager`"
+        )
+    })
+}
+
+/// The system's package manager: from `ID` / `ID_LIKE` in `/etc/os-release`
+/// when its program is installed, otherwise the first one found on PATH.
+fn detect_package_manager() -> Option<PackageManager> {
+    static DETECTED: OnceCell<Option<PackageManager>> = OnceCell::new();
+    *DETECTED.get_or_init(|| {
+        let os_release = std::fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let ids: Vec<&str> = os_release
+            .lines()
+            .filter_map(|line| line.strip_prefix("ID=").or_else(|| line.strip_prefix("ID_LIKE=")))
+            .flat_map(|value| value.trim_matches('"').split_whitespace())
+            .collect();
+        let by_distro = ids.iter().find_map(|id| match *id {
+            "debian" | "ubuntu" => Some(PackageManager::Apt),
+            "fedora" | "rhel" | "centos" => Some(PackageManager::Dnf),
+            "arch" => Some(PackageManager::Pacman),
+            "suse" | "opensuse" => Some(PackageManager::Zypper),
+            id if id.starts_with("opensuse") => Some(PackageManager::Zypper),
+            _ => None,
+        });
+        by_distro
+            .filter(|manager| on_path(manager.program()))
+            .or_else(|| PackageManager::ALL.into_iter().find(|m| on_path(m.program())))
+    })
+}
+
+/// The commands of a package step: an index refresh with `update`, then the
+/// install. Everything but brew runs through `sudo` (the session started at
+/// launch) and without prompts.
+fn package_commands(params: &PackageParams, manager: PackageManager, vars: &Vars) -> Vec<String> {
+    let packages: Vec<String> = params
+        .packages_for(manager)
+        .iter()
+        .map(|name| shell_quote(&interpolate(name, vars)))
+        .collect();
+    let (update, install) = match manager {
+        PackageManager::Apt => (
+            "sudo apt-get update",
+            "sudo env DEBIAN_FRONTEND=noninteractive apt-get install -y",
+        ),
+        PackageManager::Dnf => ("sudo dnf makecache", "sudo dnf install -y"),
+        // Arch does not support refreshing the index without upgrading (`-Sy`).
+        PackageManager::Pacman => {
+            ("sudo pacman -Syu --noconfirm", "sudo pacman -S --needed --noconfirm")
+        }
+        PackageManager::Zypper => (
+            "sudo zypper --non-interactive refresh",
+            "sudo zypper --non-interactive install",
+        ),
+        PackageManager::Brew => ("brew update", "brew install"),
+    };
+    let mut cmds = Vec::new();
+    if params.update {
+        cmds.push(update.to_string());
+    }
+    cmds.push(format!("{} {}", install, packages.join(" ")));
+    cmds
+}
+
+/// Task: download `url` to `dest` with curl, via `<dest>.part` so a failed
+/// or corrupt download never replaces the file. A `dest` that already has
+/// the expected checksum is left alone. Returns whether curl succeeded; a
+/// checksum mismatch is an error.
+fn run_download(
+    params: &DownloadParams,
+    url: &str,
+    dest: &Path,
+    step: &Step,
+    runtime: &mut StepRuntime,
+) -> Result<bool> {
+    let expected = params.sha256.as_deref().map(|hex| hex.trim().to_ascii_lowercase());
+    if let Some(expected) = &expected {
+        if dest.is_file() && sha256::file_hex(dest)? == *expected {
+            let note = format!("{} is already there with the expected checksum\n", dest.display());
+            runtime.log.push_str(&note);
+            if !dry_run() {
+                apply_mode(dest, params.mode(), &mut runtime.log)?;
+            }
+            return Ok(true);
+        }
+    }
+    if !params.force {
+        // Fail before downloading when something is in the way.
+        prepare_destination(dest, false, &mut runtime.log)?;
+    }
+
+    let part = PathBuf::from(format!("{}.part", dest.display()));
+    let cmd = format!(
+        "curl --fail --location --silent --show-error --output {} {}",
+        shell_quote(&part.to_string_lossy()),
+        shell_quote(url)
+    );
+    if dry_run() {
+        return run_logged(&cmd, step, runtime);
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    if !run_logged(&cmd, step, runtime)? {
+        let _ = std::fs::remove_file(&part);
+        return Ok(false);
+    }
+
+    let actual = sha256::file_hex(&part)?;
+    match &expected {
+        Some(expected) if *expected != actual => {
+            let _ = std::fs::remove_file(&part);
+            return Err(anyhow!(
+                "Checksum mismatch for {}: expected sha256 {}, got {}",
+                url,
+                expected,
+                actual
+            ));
+        }
+        Some(_) => runtime.log.push_str(&format!("sha256 {} matches\n", actual)),
+        None => {
+            let note = format!("sha256 {} (not checked: no sha256 given)\n", actual);
+            runtime.log.push_str(&note);
+        }
+    }
+    let mode = parse_mode(params.mode())?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&part, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set the mode of {}", part.display()))?;
+    }
+    prepare_destination(dest, params.force, &mut runtime.log)?;
+    std::fs::rename(&part, dest)
+        .with_context(|| format!("Failed to move the download to {}", dest.display()))?;
+    runtime.log.push_str(&format!("Saved {} (mode {:04o})\n", dest.display(), mode));
+    Ok(true)
+}
+
+/// `params` with vars substituted and `file` resolved against the base dir
+/// (`~` expanded).
+fn line_in_file_params(params: &LineInFileParams, ctx: &RunContext) -> LineInFileParams {
+    let mut params = params.clone();
+    params.file = expand_home(&ctx.resolve_path(&interpolate(&params.file, &ctx.vars)));
+    params.line = params.line.map(|line| interpolate(&line, &ctx.vars));
+    params.regex = params.regex.map(|regex| interpolate(&regex, &ctx.vars));
+    params
+}
+
+/// Task: make sure a line is in a file, or not. With `state: present` the
+/// last line matching `regex` (or equal to `line`) becomes `line`, and
+/// `line` is inserted when nothing matches; with `state: absent` every
+/// matching line is removed. The file is only written when it changes, so
+/// running again does nothing. A missing file counts as empty.
+fn run_line_in_file(params: &LineInFileParams, log: &mut String) -> Result<()> {
+    let regex = match &params.regex {
+        Some(pattern) => Some(
+            regex::Regex::new(pattern)
+                .with_context(|| format!("Invalid regex '{}'", pattern))?,
+        ),
+        None => None,
+    };
+    let line = params.line.as_deref();
+    let matches = |text: &str| match &regex {
+        Some(regex) => regex.is_match(text),
+        None => Some(text) == line,
+    };
+
+    let existing = read_or_empty(&params.file)?;
+    let mut lines: Vec<&str> = existing.lines().collect();
+    let description = match (params.state, line) {
+        (LineState::Present, Some(line)) => {
+            if let Some(idx) = lines.iter().rposition(|text| matches(text)) {
+                if lines[idx] == line {
+                    None
+                } else {
+                    lines[idx] = line;
+                    Some(format!("Replaced line {} of", idx + 1))
+                }
+            } else if lines.contains(&line) {
+                None
+            } else {
+                let (idx, description) = line_position(params, &lines);
+                lines.insert(idx, line);
+                Some(description)
+            }
+        }
+        _ => {
+            let before = lines.len();
+            lines.retain(|text| !matches(text));
+            match before - lines.len() {
+                0 => None,
+                removed => Some(format!("Removed {} line(s) from", removed)),
+            }
+        }
+    };
+    let Some(description) = description else {
+        log.push_str(&format!("{} is already as wanted; left unchanged.\n", params.file));
+        return Ok(());
+    };
+
+    let mut updated = lines.join("\n");
+    if !lines.is_empty() {
+        updated.push('\n');
+    }
+    if dry_run() {
+        log.push_str(&format!("Dry run, not written: {} {}\n", description, params.file));
+        log.push_str(&line_diff(&params.file, &existing, &updated));
+        return Ok(());
+    }
+    std::fs::write(&params.file, updated)
+        .with_context(|| format!("Failed to write file: {}", params.file))?;
+    log.push_str(&format!("{} {}\n", description, params.file));
+    Ok(())
 }
 
-/// Run a command through `bash -c` and stream output directly to the terminal.
-/// This is useful for long-running installs (e.g. apt-get) where we want
-/// to see progress in real time rather than only after completion.
-pub fn run_command_streaming(cmd: &str) -> Result<ExitStatus> {
-    let status = Command::new("bash")
-        .arg("-c")
-        .arg(cmd)
-        .status()
-        .with_context(|| format!("Failed to execute command: {}", cmd))?;
-    Ok(status)
+/// Where `line_in_file` adds a line nothing matched, and how to log it.
+/// Unlike `add_text`, a pattern that is not found falls back to appending.
+fn line_position(params: &LineInFileParams, lines: &[&str]) -> (usize, String) {
+    let appended = (lines.len(), "Appended a line to".to_string());
+    match params.position.as_ref().unwrap_or(&Position::Append) {
+        Position::Append => appended,
+        Position::Prepend => (0, "Prepended a line to".to_string()),
+        Position::BeforePattern(pattern) | Position::AfterPattern(pattern) => {
+            let after = matches!(params.position, Some(Position::AfterPattern(_)));
+            match lines.iter().position(|text| text.contains(pattern.as_str())) {
+                Some(idx) if after => (idx + 1, format!("Added a line after '{}' in", pattern)),
+                Some(idx) => (idx, format!("Added a line before '{}' in", pattern)),
+                None => appended,
+            }
+        }
+    }
 }
 
-fn append_output(log: &mut String, label: &str, out: &Output) {
-    use std::str;
+/// Quote `text` as a single shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
 
-    let status_code = out.status.code().unwrap_or(-1);
-    log.push_str(&format!("\n$ {}\n", label));
-    if !out.stdout.is_empty() {
-        let stdout = str::from_utf8(&out.stdout).unwrap_or("<invalid utf-8>");
-        log.push_str(stdout);
-    }
-    if !out.stderr.is_empty() {
-        let stderr = str::from_utf8(&out.stderr).unwrap_or("<invalid utf-8>");
-        log.push_str("\n[stderr]\n");
-        log.push_str(stderr);
+/// Task: add text to a file.
+fn run_add_text(params: &AddTextParams, log: &mut String) -> Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let position = params.position.clone().unwrap_or(Position::Append);
+    if matches!(position, Position::Append) && !dry_run() {
+        let existing = read_or_empty(&params.file)?;
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&params.file)
+            .with_context(|| format!("Failed to open file: {}", params.file))?;
+
+        write!(f, "{}", appended_text(params, &existing)).context("Failed to write to file")?;
+        log.push_str(&format!(
+            "Appended content to {}\n",
+            params.file
+        ));
+        return Ok(());
     }
-    log.push_str(&format!("\n[exit code: {}]\n", status_code));
-}
 
-/// Start sudo session at startup.
-pub fn start_sudo_session(log: &mut String) -> Result<()> {
-    log.push_str("Initializing sudo session with `sudo -v`...\n");
-    let output = run_command("sudo -v")?;
-    append_output(log, "sudo -v", &output);
-    if !output.status.success() {
-        return Err(anyhow!("sudo -v failed; sudo may not be available"));
+    let existing = read_or_empty(&params.file)?;
+    let (updated, description) = if let Position::Append = position {
+        let appended = appended_text(params, &existing);
+        (format!("{}{}", existing, appended), "Appended content to".to_string())
+    } else {
+        let mut lines: Vec<&str> = existing.lines().collect();
+        let (idx, description) = insertion_point(params, &position, &lines)?;
+        lines.insert(idx, params.content.as_str());
+
+        let mut updated = lines.join("\n");
+        updated.push('\n');
+        (updated, description)
+    };
+
+    if dry_run() {
+        log.push_str(&format!("Dry run, not written: {} {}\n", description, params.file));
+        log.push_str(&line_diff(&params.file, &existing, &updated));
+        return Ok(());
     }
+    std::fs::write(&params.file, updated)
+        .with_context(|| format!("Failed to write file: {}", params.file))?;
+    log.push_str(&format!("{} {}\n", description, params.file));
     Ok(())
 }
 
-/// Run a single step (pre/script/post + task-specific logic).  
-/// Returns updated StepRuntime.
-pub fn run_step(step: &Step, runtime: &mut StepRuntime) -> Result<()> {
-    runtime.status = StepStatus::Running;
-    runtime.log.push_str(&format!("== Running step: {} ==\n", step.name));
+/// Task: substitute text in an existing file.
+/// The file is written by the wizard itself, or with `sudo` / `run_as`
+/// through `write_elevated`; returns whether that write succeeded.
+fn run_replace_in_file(
+    params: &ReplaceInFileParams,
+    step: &Step,
+    runtime: &mut StepRuntime,
+) -> Result<bool> {
+    let log = &mut runtime.log;
+    let text = std::fs::read_to_string(&params.file)
+        .with_context(|| format!("Failed to read file: {}", params.file))?;
+    let limit = params.count.unwrap_or(usize::MAX);
 
-    // Run pre_script if any.
-    if let Some(pre) = &step.pre_script {
-        runtime.log.push_str("\n--- pre_script ---\n");
-        let out = run_command(pre)?;
-        append_output(&mut runtime.log, pre, &out);
-        if !out.status.success() {
-            runtime.log.push_str("\npre_script failed; step will be skipped.\n");
-            runtime.status = StepStatus::Skipped;
-            return Ok(());
+    let (updated, replaced) = if params.regex {
+        let re = regex::Regex::new(&params.find)
+            .with_context(|| format!("Invalid regex '{}'", params.find))?;
+        let replaced = re.find_iter(&text).count().min(limit);
+        (re.replacen(&text, limit, params.replace.as_str()).into_owned(), replaced)
+    } else {
+        let replaced = text.matches(params.find.as_str()).count().min(limit);
+        (text.replacen(params.find.as_str(), &params.replace, limit), replaced)
+    };
+
+    if replaced == 0 {
+        if params.require_match {
+            return Err(anyhow!("'{}' not found in {}", params.find, params.file));
+        }
+        log.push_str(&format!("No match for '{}'; file left unchanged.\n", params.find));
+        return Ok(true);
+    }
+    if dry_run() {
+        log.push_str(&format!(
+            "Dry run, not written: Replaced {} occurrence(s) of '{}' in {}\n",
+            replaced, params.find, params.file
+        ));
+        log.push_str(&line_diff(&params.file, &text, &updated));
+        return Ok(true);
+    }
+    if params.sudo || step.run_as.is_some() {
+        if !write_elevated(&params.file, &updated, params.sudo, step, runtime)? {
+            return Ok(false);
         }
+    } else {
+        std::fs::write(&params.file, updated)
+            .with_context(|| format!("Failed to write file: {}", params.file))?;
     }
+    runtime.log.push_str(&format!(
+        "Replaced {} occurrence(s) of '{}' in {}\n",
+        replaced, params.find, params.file
+    ));
+    Ok(true)
+}
 
-    // Dispatch main task depending on type.
-    match &step.kind {
-        StepKind::Script => {
-            if let Some(script) = &step.script {
-                runtime.log.push_str("\n--- script ---\n");
-                let out = run_command(script)?;
-                append_output(&mut runtime.log, script, &out);
-                if !out.status.success() {
-                    runtime.status = StepStatus::Failed;
-                    return Ok(());
-                }
-            } else {
-                runtime.log.push_str("\nNo script specified for script step.\n");
+/// Write `text` to `file` with a logged `tee` command run as the step's
+/// `run_as` user and/or through `sudo`, for files the wizard cannot write
+/// itself. `tee` keeps the file's owner and mode. The text goes through a
+/// temporary file in a fresh directory of our own, so nobody else can plant
+/// or swap it; both are readable by others only when the `run_as` user needs it.
+fn write_elevated(
+    file: &str,
+    text: &str,
+    sudo: bool,
+    step: &Step,
+    runtime: &mut StepRuntime,
+) -> Result<bool> {
+    use std::fs::{DirBuilder, OpenOptions, Permissions};
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "wiza-rs-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    ));
+    // Fails if the path exists already, whatever it is.
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let tmp = dir.join("contents");
+    let written = (|| -> std::io::Result<()> {
+        let mut out = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp)?;
+        out.write_all(text.as_bytes())?;
+        if step.run_as.is_some() {
+            std::fs::set_permissions(&dir, Permissions::from_mode(0o711))?;
+            out.set_permissions(Permissions::from_mode(0o644))?;
+        }
+        Ok(())
+    })();
+    let res = written
+        .with_context(|| format!("Failed to write {}", tmp.display()))
+        .and_then(|()| {
+            let cmd = format!(
+                "{}tee {} < {} > /dev/null",
+                if sudo { "sudo " } else { "" },
+                shell_quote(file),
+                shell_quote(&tmp.to_string_lossy())
+            );
+            run_logged(&cmd, step, runtime)
+        });
+    let _ = std::fs::remove_file(&tmp);
+    let _ = std::fs::remove_dir(&dir);
+    res
+}
+
+/// What `append` adds to a file currently holding `existing`, with the
+/// `leading_newline` / `trailing_newline` options applied.
+fn appended_text(params: &AddTextParams, existing: &str) -> String {
+    let mut text = String::new();
+    if params.leading_newline && !existing.is_empty() && !existing.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&params.content);
+    if params.trailing_newline.unwrap_or(true) {
+        text.push('\n');
+    }
+    text
+}
+
+/// The contents of `file`, or nothing if it does not exist yet.
+fn read_or_empty(file: &str) -> Result<String> {
+    match std::fs::read_to_string(file) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read file: {}", file)),
+    }
+}
+
+/// A minimal line diff of `before` -> `after` for dry runs: the common head
+/// and tail are left out and the lines in between are listed as `-` removed
+/// and `+` added. When as many lines are removed as added, only the lines
+/// that differ are listed, each under its own `@@ line N @@`.
+fn line_diff(file: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (removed, added) = (&old[head..old.len() - tail], &new[head..new.len() - tail]);
+
+    let mut diff = format!("--- {}\n+++ {} (dry run)\n", file, file);
+    if removed.is_empty() && added.is_empty() {
+        diff.push_str("(no changes)\n");
+    } else if removed.len() == added.len() {
+        for (i, (old, new)) in removed.iter().zip(added).enumerate() {
+            if old != new {
+                diff.push_str(&format!("@@ line {} @@\n-{}\n+{}\n", head + i + 1, old, new));
             }
         }
-        StepKind::AddText { params } => {
-            runtime.log
-                .push_str(&format!("\n--- add_text to {} ---\n", params.file));
-            run_add_text(params, &mut runtime.log)?;
+    } else {
+        diff.push_str(&format!("@@ line {} @@\n", head + 1));
+        for line in removed {
+            diff.push_str(&format!("-{}\n", line));
         }
-        StepKind::GitConfig { params: _ } => {
-            // For git_config, the interactive UI (ratatui) is responsible for
-            // gathering values and invoking the actual configuration logic.
-            runtime.log.push_str("\n--- git_config (handled by TUI) ---\n");
+        for line in added {
+            diff.push_str(&format!("+{}\n", line));
         }
-        StepKind::AppSelection { params: _ } => {
-            // For app_selection, the interactive UI (ratatui) is responsible for
-            // gathering the selection and invoking the actual installation logic.
-            runtime.log.push_str("\n--- app_selection (handled by TUI) ---\n");
+    }
+    diff
+}
+
+/// Where `params.content` goes among the file's `lines`, with a description
+/// of the edit for the step log.
+fn insertion_point(
+    params: &AddTextParams,
+    position: &Position,
+    lines: &[&str],
+) -> Result<(usize, String)> {
+    match position {
+        Position::Append => Ok((lines.len(), "Appended content to".to_string())),
+        Position::Prepend => Ok((0, "Prepended content to".to_string())),
+        Position::BeforePattern(pattern) | Position::AfterPattern(pattern) => {
+            let after = matches!(position, Position::AfterPattern(_));
+            match lines.iter().position(|line| line.contains(pattern.as_str())) {
+                Some(idx) => Ok((
+                    if after { idx + 1 } else { idx },
+                    format!(
+                        "Inserted content {} line matching '{}' in",
+                        if after { "after" } else { "before" },
+                        pattern
+                    ),
+                )),
+                None if params.fallback_append => Ok((
+                    lines.len(),
+                    format!("Pattern '{}' not found; appended content to", pattern),
+                )),
+                None => Err(anyhow!("Pattern '{}' not found in {}", pattern, params.file)),
+            }
         }
     }
+}
 
-    // Run post_script if any.
-    if let Some(post) = &step.post_script {
-        runtime.log.push_str("\n--- post_script ---\n");
-        let out = run_command(post)?;
-        append_output(&mut runtime.log, post, &out);
-        if !out.status.success() {
-            runtime.status = StepStatus::Failed;
-            return Ok(());
+/// What an `add_text` step would do to its file, without touching it.
+#[derive(Debug, Clone)]
+pub struct AddTextPreview {
+    /// Resolved target path.
+    pub file: String,
+    /// The file as it would look afterwards.
+    pub lines: Vec<String>,
+    /// Indices in `lines` of the inserted content.
+    pub inserted: std::ops::Range<usize>,
+    pub description: String,
+}
+
+/// Compute the `add_text` edit for `params` the same way running the step
+/// would, including interpolation and path resolution.
+pub fn preview_add_text(params: &AddTextParams, ctx: &RunContext) -> Result<AddTextPreview> {
+    let file = ctx.resolve_path(&interpolate(&params.file, &ctx.vars));
+    let content = interpolate(&params.content, &ctx.vars);
+    let existing = read_or_empty(&file)?;
+
+    let position = params.position.clone().unwrap_or(Position::Append);
+    let current: Vec<&str> = existing.lines().collect();
+    let (idx, description) = insertion_point(params, &position, &current)?;
+    let added: Vec<&str> = content.lines().collect();
+    let mut lines: Vec<String> = current.iter().map(|line| line.to_string()).collect();
+    lines.splice(idx..idx, added.iter().map(|line| line.to_string()));
+    Ok(AddTextPreview {
+        inserted: idx..idx + added.len(),
+        file,
+        lines,
+        description,
+    })
+}
+
+/// The source and dest paths of a template step, with vars substituted and
+/// resolved against the base dir; `~` is expanded.
+fn template_paths(params: &TemplateParams, ctx: &RunContext) -> (PathBuf, PathBuf) {
+    let path = |text: &str| {
+        PathBuf::from(expand_home(&ctx.resolve_path(&interpolate(text, &ctx.vars))))
+    };
+    (path(&params.source), path(&params.dest))
+}
+
+/// Task: render the template at `source` with the step's vars over `vars`
+/// and write it to `dest`. A `dest` that already has the rendered contents
+/// is left alone; one that differs is only replaced with `force`. References
+/// no var defines are kept as they are: environment variables (e.g. shell
+/// `${PATH}` in a `.zshrc`) are listed in the log, and any other name is
+/// reported with its line, or fails the step under `--strict`.
+fn run_template(
+    params: &TemplateParams,
+    source: &Path,
+    dest: &Path,
+    ctx: &RunContext,
+    log: &mut String,
+) -> Result<()> {
+    let template = std::fs::read_to_string(source)
+        .with_context(|| format!("Failed to read template {}", source.display()))?;
+    let mut all = ctx.vars.clone();
+    all.extend(params.vars.iter().map(|(k, v)| (k.clone(), interpolate(v, &ctx.vars))));
+    let rendered = interpolate(&template, &all);
+
+    let (mut shell, mut undefined) = (Vec::new(), Vec::new());
+    for (n, line) in template.lines().enumerate() {
+        for name in var_references(&interpolate(line, &all)) {
+            let place = format!("${{{}}} on line {}", name, n + 1);
+            if std::env::var_os(name).is_some() {
+                shell.push(place);
+            } else {
+                undefined.push(place);
+            }
         }
     }
+    if !undefined.is_empty() && ctx.strict {
+        return Err(anyhow!(
+            "template {}: undefined variable(s) {}; set them in `vars` (of the file or the step), \
+             `env_file` or the environment",
+            source.display(),
+            undefined.join(", ")
+        ));
+    }
+    for place in &undefined {
+        log.push_str(&format!(
+            "[warning] template {}: {} is not a var or environment variable; left as-is\n",
+            source.display(),
+            place
+        ));
+    }
+    if !shell.is_empty() {
+        log.push_str(&format!(
+            "Left as-is for the shell (environment variables): {}\n",
+            shell.join(", ")
+        ));
+    }
 
-    if runtime.status == StepStatus::Running {
-        runtime.status = StepStatus::Success;
+    let existing = match std::fs::read_to_string(dest) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(_) if dest.is_dir() => {
+            return Err(anyhow!("Destination {} is a directory", dest.display()));
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dest.display())),
+    };
+    if existing.as_deref() == Some(rendered.as_str()) {
+        log.push_str(&format!("{} is up to date\n", dest.display()));
+        if let (Some(mode), false) = (&params.mode, dry_run()) {
+            apply_mode(dest, mode, log)?;
+        }
+        return Ok(());
+    }
+    if dry_run() {
+        let file = dest.display().to_string();
+        log.push_str(&format!("Dry run, not written: {}\n", file));
+        log.push_str(&line_diff(&file, existing.as_deref().unwrap_or(""), &rendered));
+        return Ok(());
+    }
+    prepare_destination(dest, params.force, log)?;
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(dest, rendered)
+        .with_context(|| format!("Failed to write file: {}", dest.display()))?;
+    log.push_str(&format!("Rendered {} into {}\n", source.display(), dest.display()));
+    if let Some(mode) = &params.mode {
+        apply_mode(dest, mode, log)?;
+    }
+    Ok(())
+}
+
+/// Give `path` the permissions `mode` (octal digits, see `parse_mode`),
+/// logging it when they change.
+fn apply_mode(path: &Path, mode: &str, log: &mut String) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = parse_mode(mode)?;
+    let meta = std::fs::metadata(path)
+        .with_context(|| format!("Failed to inspect {}", path.display()))?;
+    if meta.permissions().mode() & 0o7777 != mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set the mode of {}", path.display()))?;
+        log.push_str(&format!("Set mode {:04o} on {}\n", mode, path.display()));
     }
+    Ok(())
+}
 
+/// The (source, target) paths of a symlink step, with vars substituted and
+/// resolved against the base dir; `~` is expanded.
+fn symlink_paths(params: &SymlinkParams, ctx: &RunContext) -> Vec<(PathBuf, PathBuf)> {
+    let path = |text: &str| {
+        PathBuf::from(expand_home(&ctx.resolve_path(&interpolate(text, &ctx.vars))))
+    };
+    params
+        .links
+        .iter()
+        .map(|link| (path(&link.source), path(&link.target)))
+        .collect()
+}
+
+/// Task: create each `target -> source` link. A target that already links
+/// to its source is left alone. Other conflicts (a missing source, something
+/// in the way without `force` / `backup`) are logged and the remaining links
+/// still made; the step then fails naming how many went wrong.
+fn run_symlink(
+    links: &[(PathBuf, PathBuf)],
+    params: &SymlinkParams,
+    log: &mut String,
+) -> Result<()> {
+    let mut conflicts = 0;
+    for (source, target) in links {
+        if let Err(e) = make_link(source, target, params, log) {
+            log.push_str(&format!("[conflict] {}: {:#}\n", target.display(), e));
+            conflicts += 1;
+        }
+    }
+    if conflicts > 0 {
+        return Err(anyhow!("{} of {} link(s) could not be created", conflicts, links.len()));
+    }
     Ok(())
 }
 
-/// Task: add text to a file.
-fn run_add_text(params: &AddTextParams, log: &mut String) -> Result<()> {
-    use std::fs::OpenOptions;
-    use std::io::Write;
+fn make_link(
+    source: &Path,
+    target: &Path,
+    params: &SymlinkParams,
+    log: &mut String,
+) -> Result<()> {
+    if std::fs::read_link(target).is_ok_and(|current| current == source) {
+        log.push_str(&format!("{} already links to {}\n", target.display(), source.display()));
+        return Ok(());
+    }
+    if std::fs::symlink_metadata(source).is_err() {
+        return Err(anyhow!("source {} does not exist", source.display()));
+    }
+    if dry_run() {
+        log.push_str(&format!(
+            "Would link {} -> {} (dry run)\n",
+            target.display(),
+            source.display()
+        ));
+        return Ok(());
+    }
+    if params.backup && std::fs::symlink_metadata(target).is_ok() {
+        let backup = backup_path(target);
+        std::fs::rename(target, &backup)
+            .with_context(|| format!("Failed to move {} aside", target.display()))?;
+        log.push_str(&format!("Moved {} to {}\n", target.display(), backup.display()));
+    } else {
+        prepare_destination(target, params.force, log)?;
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::os::unix::fs::symlink(source, target)
+        .with_context(|| format!("Failed to create the link {}", target.display()))?;
+    log.push_str(&format!("Linked {} -> {}\n", target.display(), source.display()));
+    Ok(())
+}
 
-    let mut f = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&params.file)
-        .with_context(|| format!("Failed to open file: {}", params.file))?;
+/// `<path>.bak`, or `<path>.bak.N` for the first N that is free.
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path.as_os_str().to_string_lossy();
+    let first = PathBuf::from(format!("{}.bak", name));
+    if std::fs::symlink_metadata(&first).is_err() {
+        return first;
+    }
+    (1..)
+        .map(|n| PathBuf::from(format!("{}.bak.{}", name, n)))
+        .find(|candidate| std::fs::symlink_metadata(candidate).is_err())
+        .expect("some backup name is free")
+}
 
-    writeln!(f, "{}", params.content).context("Failed to write to file")?;
+/// Shared overwrite policy for steps that create a file at `dest`
+/// (download, symlink, template). A missing destination is fine. An existing
+/// file or symlink fails the step unless `force` is set, in which case it is
+/// moved aside to `<dest>.bak` (see `backup_path`) and the move is logged.
+/// Directories are never replaced.
+fn prepare_destination(dest: &Path, force: bool, log: &mut String) -> Result<()> {
+    let meta = match std::fs::symlink_metadata(dest) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to inspect {}", dest.display()));
+        }
+    };
+
+    if meta.is_dir() {
+        return Err(anyhow!(
+            "Destination {} is a directory; refusing to replace it",
+            dest.display()
+        ));
+    }
+    if !force {
+        return Err(anyhow!(
+            "Destination {} already exists; set `force: true` to overwrite it",
+            dest.display()
+        ));
+    }
+
+    let backup = backup_path(dest);
+    std::fs::rename(dest, &backup)
+        .with_context(|| format!("Failed to move existing {} aside", dest.display()))?;
+    let what = if meta.file_type().is_symlink() {
+        "symlink"
+    } else {
+        "file"
+    };
     log.push_str(&format!(
-        "Appended content to {}\n",
-        params.file
+        "Moved existing {} at {} to {} (force)\n",
+        what,
+        dest.display(),
+        backup.display()
     ));
     Ok(())
 }
 
-/// Task: git config (name, email, editor).
-/// The ratatui layer gathers the values; this helper simply applies them.
-pub fn apply_git_config(
+/// Task: prompt. Store the collected `values` (one per field) in the vars map.
+pub fn apply_prompt(
+    params: &PromptParams,
+    values: Vec<String>,
+    runtime: &mut StepRuntime,
+    ctx: &mut RunContext,
+) {
+    for (field, value) in params.fields.iter().zip(values) {
+        let shown = if field.secret { REDACTED } else { value.as_str() };
+        runtime
+            .log
+            .push_str(&format!("Set ${{{}}} = {}\n", field.var, shown));
+        if field.secret {
+            ctx.secrets.insert(field.var.clone());
+        }
+        ctx.vars.insert(field.var.clone(), value);
+    }
+    if runtime.status == StepStatus::Running {
+        runtime.status = StepStatus::Success;
+    }
+}
+
+/// The editor the git form sets: what was typed, else the step's
+/// `default_editor`.
+pub fn git_editor<'a>(params: &'a GitConfigParams, editor: &'a str) -> &'a str {
+    if editor.trim().is_empty() {
+        params.default_editor.trim()
+    } else {
+        editor.trim()
+    }
+}
+
+/// The `git config --global` commands that set the values from the git form;
+/// the editor falls back to the step's `default_editor`. Under `--strict`
+/// (`strict`), an editor that is not installed is an error.
+pub fn git_config_commands(
     params: &GitConfigParams,
     name: &str,
     email: &str,
     editor: &str,
-    log: &mut String,
-) -> Result<()> {
+    strict: bool,
+) -> Result<Vec<String>> {
     let name = name.trim();
     let email = email.trim();
-    let editor = if editor.trim().is_empty() {
-        params.default_editor.trim()
-    } else {
-        editor.trim()
-    };
+    let editor = git_editor(params, editor);
 
     if name.is_empty() {
         return Err(anyhow!("Git user.name cannot be empty"));
@@ -163,28 +2342,59 @@ pub fn apply_git_config(
     if email.is_empty() {
         return Err(anyhow!("Git user.email cannot be empty"));
     }
+    if editor.is_empty() {
+        return Err(anyhow!("Git core.editor cannot be empty"));
+    }
+    for (field, value) in [("user.name", name), ("user.email", email), ("core.editor", editor)] {
+        if value.contains(['\n', '\r']) {
+            return Err(anyhow!("Git {} cannot contain a line break", field));
+        }
+    }
+    if strict {
+        if let Some(problem) = missing_editor(editor) {
+            return Err(anyhow!("{} (--strict)", problem));
+        }
+    }
 
-    let commands = vec![
-        format!("git config --global user.name '{}'", name.replace('\'', "\\'")),
-        format!(
-            "git config --global user.email '{}'",
-            email.replace('\'', "\\'")
-        ),
-        format!(
-            "git config --global core.editor '{}'",
-            editor.replace('\'', "\\'")
-        ),
-    ];
+    Ok(vec![
+        format!("git config --global user.name {}", shell_quote(name)),
+        format!("git config --global user.email {}", shell_quote(email)),
+        format!("git config --global core.editor {}", shell_quote(editor)),
+    ])
+}
+
+/// Task: git config (name, email, editor).
+/// The ratatui layer gathers the values; this helper simply applies them.
+pub fn apply_git_config(
+    params: &GitConfigParams,
+    name: &str,
+    email: &str,
+    editor: &str,
+    strict: bool,
+    runtime: &mut StepRuntime,
+) -> Result<()> {
+    let commands = git_config_commands(params, name, email, editor, strict)?;
+    if let Some(problem) = missing_editor(git_editor(params, editor)) {
+        runtime.log.push_str(&format!("[warning] {}\n", problem));
+    }
 
+    if dry_run() {
+        for cmd in &commands {
+            log_dry_run(&mut runtime.log, cmd);
+        }
+        runtime.log.push_str("Git configuration left unchanged (dry run).\n");
+        return Ok(());
+    }
     for cmd in commands {
+        runtime.set_current_command(Some(&cmd));
         let out = run_command(&cmd)?;
-        append_output(log, &cmd, &out);
+        record_output(runtime, &cmd, &out);
         if !out.status.success() {
             return Err(anyhow!("Command failed: {}", cmd));
         }
     }
 
-    log.push_str("Git configuration updated.\n");
+    runtime.log.push_str("Git configuration updated.\n");
     Ok(())
 }
 
@@ -194,31 +2404,274 @@ pub fn apply_git_config(
 pub fn apply_app_selection(
     params: &AppSelectionParams,
     selection: &[usize],
-    log: &mut String,
+    step: &Step,
+    runtime: &mut StepRuntime,
+    ctx: &RunContext,
 ) -> Result<()> {
+    let run_as = step.run_as.as_deref();
     if params.apps.is_empty() {
-        log.push_str("No apps defined in this step.\n");
+        runtime.log.push_str("No apps defined in this step.\n");
         return Ok(());
     }
 
     if selection.is_empty() {
-        log.push_str("No apps selected.\n");
+        runtime.log.push_str("No apps selected.\n");
         return Ok(());
     }
 
+    // Substitute every command up front, so an undefined variable fails the
+    // step before anything is installed.
+    let mut installs = Vec::new();
     for &idx in selection {
         if let Some(app) = params.apps.get(idx) {
-            log.push_str(&format!(
-                "Installing {} ({}) using: {}\n",
-                app.name, app.version, app.install
-            ));
-            let status = run_command_streaming(&app.install)?;
-            if !status.success() {
-                log.push_str(&format!("Installation of {} failed.\n", app.name));
-                // continue to attempt next app, but keep note the failure.
-            }
+            let install = params
+                .install_command(app)
+                .map(|install| interpolate_defined(&install, &ctx.vars))
+                .transpose()
+                .with_context(|| format!("install command of app '{}'", app.name))?;
+            installs.push((app, install));
+        }
+    }
+
+    runtime.update_installs(|progress| {
+        *progress = InstallProgress {
+            total: installs.len(),
+            ..Default::default()
         }
+    });
+    for (app, install) in installs {
+        let Some(install) = install else {
+            runtime
+                .log
+                .push_str(&format!("No install command for {}; skipped.\n", app.name));
+            runtime.update_installs(|progress| progress.done += 1);
+            continue;
+        };
+        // What the log and the failure details show: secrets are masked.
+        let shown = ctx.redact(&install);
+        runtime.log.push_str(&format!(
+            "Installing {} ({}) using: {}\n",
+            app.name, app.version, shown
+        ));
+        if let Some(user) = run_as {
+            runtime.log.push_str(&format!("[running as user '{}']\n", user));
+        }
+        if dry_run() {
+            log_dry_run(&mut runtime.log, &shown);
+            runtime.update_installs(|progress| progress.done += 1);
+            continue;
+        }
+        runtime.set_current_command(Some(&shown));
+        let timeout = step_timeout(step);
+        log_verbose(&mut runtime.log, || {
+            format!(
+                "shell: {}, timeout: {}",
+                shell_description(run_as),
+                timeout.map_or("none".to_string(), |t| format!("{}s", t.as_secs()))
+            )
+        });
+        // Only with `interactive` is the TUI suspended; on a worker the output
+        // is captured into the log.
+        let res = if step.interactive {
+            run_command_streaming(&install, true, run_as, timeout).map(|status| {
+                record_status(runtime, &shown, &status);
+                status.success()
+            })
+        } else {
+            run_command_live(&install, run_as, timeout, Some(&runtime.live_log)).map(|out| {
+                record_output(runtime, &shown, &out);
+                out.status.success()
+            })
+        };
+        let succeeded = match res {
+            Err(e) if e.is::<CommandTimeout>() => record_timeout(runtime, &shown, &e),
+            res => res?,
+        };
+        if !succeeded {
+            runtime.log.push_str(&format!("Installation of {} failed.\n", app.name));
+            // continue to attempt next app, but keep note the failure.
+        }
+        runtime.update_installs(|progress| {
+            progress.done += 1;
+            progress.failed += usize::from(!succeeded);
+        });
     }
+    runtime.set_current_command(None);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    fn output(stdout: &[u8], stderr: &[u8]) -> Output {
+        Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: stdout.to_vec(),
+            stderr: stderr.to_vec(),
+        }
+    }
+
+    #[test]
+    fn append_output_replaces_invalid_utf8_and_keeps_the_rest() {
+        let mut log = String::new();
+        append_output(&mut log, "tool", &output(b"caf\xe9 ok\n", b"bad \xff\xfe end\n"));
+        assert!(log.contains("caf\u{fffd} ok"), "{}", log);
+        assert!(log.contains("bad \u{fffd}\u{fffd} end"), "{}", log);
+        assert!(log.contains("[exit code: 0]"));
+    }
+
+    #[test]
+    fn wait_for_drains_both_pipes_without_deadlock() {
+        // Far more than a pipe buffer (64 KiB) on each stream, stderr first:
+        // reading stdout to the end before stderr would hang.
+        let cmd = "yes | head -c 1000000 >&2; yes | head -c 1000000";
+        let out = run_command_as(cmd, None, Some(Duration::from_secs(30))).unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout.len(), 1_000_000);
+        assert_eq!(out.stderr.len(), 1_000_000);
+    }
+
+    #[test]
+    fn run_step_errors_name_the_step_and_its_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let step: Step =
+            serde_yaml::from_str("{name: Setup, type: script, script_file: missing.sh}").unwrap();
+        let mut ctx = RunContext::new(Vars::new());
+        ctx.base_dir = dir.path().to_path_buf();
+        let err = run_step(&step, &mut StepRuntime::default(), &mut ctx).unwrap_err();
+        let chain = format!("{:#}", err);
+        assert!(chain.starts_with("step 'Setup' (script): "), "{}", chain);
+        assert!(chain.contains("Failed to read script file"), "{}", chain);
+        assert!(chain.contains("missing.sh"), "{}", chain);
+    }
+
+    #[test]
+    fn worker_output_reaches_the_live_log_while_the_command_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let go = dir.path().join("go");
+        let script = format!(
+            "echo first; while [ ! -e '{}' ]; do sleep 0.05; done; echo second",
+            go.display()
+        );
+        let yaml = format!("{{name: Slow, type: script, script: \"{}\"}}", script);
+        let step: Step = serde_yaml::from_str(&yaml).unwrap();
+        let runtime = StepRuntime::default();
+        let mut worker = runtime.clone();
+        let handle = thread::spawn(move || {
+            run_step(&step, &mut worker, &mut RunContext::new(Vars::new())).map(|()| worker)
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !runtime.live_log().contains("first\n") {
+            assert!(Instant::now() < deadline, "no live output: {:?}", runtime.live_log());
+            thread::sleep(Duration::from_millis(20));
+        }
+        let live = runtime.live_log();
+        assert!(live.contains("== Running step: Slow"), "{}", live);
+        assert!(!live.contains("\nsecond\n"), "{}", live);
+        assert!(!handle.is_finished());
+
+        std::fs::write(&go, "").unwrap();
+        let worker = handle.join().unwrap().unwrap();
+        assert!(worker.log.contains("first\nsecond\n"), "{}", worker.log);
+        assert_eq!(runtime.live_log(), worker.log);
+    }
+
+    #[test]
+    fn live_log_drops_whole_lines_from_the_front() {
+        let live = Mutex::new(String::new());
+        for i in 0..LIVE_LOG_LIMIT / 10 + 100 {
+            append_live(&live, format!("line {:04}\n", i % 10_000).as_bytes());
+        }
+        let live = live.into_inner().unwrap();
+        assert!(live.len() <= LIVE_LOG_LIMIT, "{}", live.len());
+        assert!(live.starts_with("line "), "{:?}", &live[..20]);
+        assert!(live.ends_with(&format!("line {:04}\n", (LIVE_LOG_LIMIT / 10 + 99) % 10_000)));
+    }
+
+    fn app_step(params: &str) -> (Step, AppSelectionParams) {
+        let yaml = format!("{{name: Apps, type: app_selection, params: {}}}", params);
+        let step: Step = serde_yaml::from_str(&yaml).unwrap();
+        let StepKind::AppSelection { params } = &step.kind else { unreachable!() };
+        let params = params.clone();
+        (step, params)
+    }
+
+    #[test]
+    fn app_install_commands_substitute_vars() {
+        let (step, params) = app_step(
+            "{command_template: 'echo ${PREFIX}-{pkg}', apps: [{name: htop, version: '1'}, \
+             {name: jq, version: '1', install: 'echo ${ARCH}'}]}",
+        );
+        let vars = Vars::from([
+            ("PREFIX".to_string(), "pkg".to_string()),
+            ("ARCH".to_string(), "arm64".to_string()),
+        ]);
+        let mut runtime = StepRuntime::default();
+        apply_app_selection(&params, &[0, 1], &step, &mut runtime, &RunContext::new(vars))
+            .unwrap();
+        let log = &runtime.log;
+        assert!(log.contains("Installing htop (1) using: echo pkg-htop\n"), "{}", log);
+        assert!(log.contains("Installing jq (1) using: echo arm64\n"), "{}", log);
+        assert!(log.contains("\narm64\n"), "{}", log);
+    }
+
+    #[test]
+    fn app_install_with_an_undefined_var_fails_before_installing() {
+        let (step, params) = app_step(
+            "{apps: [{name: ok, version: '1', install: 'true'}, \
+             {name: tool, version: '1', install: 'echo ${WIZA_TEST_UNSET_VAR}'}]}",
+        );
+        let mut runtime = StepRuntime::default();
+        let ctx = RunContext::new(Vars::new());
+        let err = apply_app_selection(&params, &[0, 1], &step, &mut runtime, &ctx).unwrap_err();
+        let chain = format!("{:#}", err);
+        assert!(chain.starts_with("install command of app 'tool': "), "{}", chain);
+        assert!(chain.contains("undefined variable(s) ${WIZA_TEST_UNSET_VAR}"), "{}", chain);
+        assert!(!runtime.log.contains("Installing"), "{}", runtime.log);
+    }
+
+    #[test]
+    fn prepare_destination_leaves_a_missing_destination_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = String::new();
+        prepare_destination(&dir.path().join("new"), false, &mut log).unwrap();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn prepare_destination_refuses_an_existing_file_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file");
+        std::fs::write(&dest, "keep").unwrap();
+        let mut log = String::new();
+        let err = prepare_destination(&dest, false, &mut log).unwrap_err();
+        assert!(err.to_string().contains("set `force: true`"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "keep");
+    }
+
+    #[test]
+    fn prepare_destination_backs_up_an_existing_file_with_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("file");
+        std::fs::write(&dest, "old").unwrap();
+        std::fs::write(dir.path().join("file.bak"), "older").unwrap();
+        let mut log = String::new();
+        prepare_destination(&dest, true, &mut log).unwrap();
+        assert!(!dest.exists());
+        let backup = dir.path().join("file.bak.1");
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "old");
+        let expected = format!("Moved existing file at {} to {}", dest.display(), backup.display());
+        assert!(log.contains(&expected), "{}", log);
+    }
+
+    #[test]
+    fn prepare_destination_never_replaces_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = prepare_destination(dir.path(), true, &mut String::new()).unwrap_err();
+        assert!(err.to_string().contains("is a directory"), "{}", err);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::cli::{AppsPolicy, Cli, OutputFormat};
+use crate::events::EventSink;
+use crate::executor::{apply_app_selection, apply_prompt, run_step, GlobalTimeout, RunContext};
+use crate::model::{
+    dependencies, next_ready, Step, StepFile, StepKind, StepRuntime, StepStatus, ThemePreset,
+};
+use crate::notify;
+use crate::report;
+use crate::state::RunState;
+use anyhow::{anyhow, Result};
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+/// Run every step in order without the TUI (`--headless`), printing each
+/// step's log when it finishes. Stops at the first failure of a non-optional
+/// step; steps never reached end up `NotRun`. Fails if any non-optional step
+/// failed. With `--run-step`, only that step (after its dependencies, with
+/// `--with-deps`) runs and is reported on.
+pub fn run(steps_file: &StepFile, cli: &Cli) -> Result<()> {
+    let steps = &steps_file.steps;
+    // A `mono` preset in the file turns colours off here too.
+    let mono = cli.theme.or(steps_file.theme.preset) == Some(ThemePreset::Mono);
+    let printer = Printer::new(cli.format, cli.color() && !mono);
+    let mut events = cli.events.as_deref().map(EventSink::open).transpose()?;
+    let mut emit = |idx: usize, status: StepStatus| {
+        if let Some(sink) = &mut events {
+            if let Err(e) = sink.emit(idx, &steps[idx].name, status) {
+                eprintln!("[events] {:#}; no further events will be written.", e);
+                events = None;
+            }
+        }
+    };
+
+    let deps = dependencies(steps);
+    let plan = match &cli.run_step {
+        Some(name) => Some(plan_step(steps, &deps, name, cli.with_deps)?),
+        None => None,
+    };
+
+    let mut setup_log = String::new();
+    let mut ctx = RunContext::prepare(steps_file, cli, &mut setup_log)?;
+    printer.block("wiza-rs", StepStatus::Running, &setup_log);
+
+    let mut runtimes = vec![StepRuntime::default(); steps.len()];
+    let mut durations = vec![None; steps.len()];
+    // `--run-step` is a side trip: it leaves the saved progress alone.
+    let mut state = RunState::for_file(&cli.yaml_path).filter(|_| plan.is_none());
+    if let (true, Some(state)) = (cli.resume, &state) {
+        let note = state.resume(steps, &mut runtimes);
+        printer.block("wiza-rs", StepStatus::Running, &note);
+    }
+    let next = |runtimes: &[StepRuntime]| match &plan {
+        // Dependencies come first in the plan, so the first pending step is next.
+        Some(plan) => plan.iter().copied().find(|&i| runtimes[i].status == StepStatus::Pending),
+        None => next_ready(&deps, runtimes),
+    };
+    let mut timed_out = None;
+    while let Some(idx) = next(&runtimes) {
+        let step = &steps[idx];
+        let rt = &mut runtimes[idx];
+        rt.status = StepStatus::Running;
+        emit(idx, rt.status);
+
+        let started = Instant::now();
+        let res = run_headless_step(step, rt, &mut ctx, cli.apps);
+        durations[idx] = Some(started.elapsed());
+        let mut stop = None;
+        if let Err(e) = res {
+            rt.status = StepStatus::Failed;
+            rt.log.push_str(&ctx.redact(&format!("\n[ERROR] {:#}\n", e)));
+            stop = Some(e).filter(|e| e.is::<GlobalTimeout>());
+        }
+        emit(idx, rt.status);
+        printer.block(&step.name, rt.status, &rt.log);
+        if stop.is_some() {
+            let note = "Global timeout reached; aborting the run.\n";
+            printer.block("wiza-rs", StepStatus::Failed, note);
+            timed_out = stop;
+            break;
+        }
+        if let Some(saved) = &state {
+            if let Err(e) = saved.save(steps, &runtimes) {
+                eprintln!("[continue] {:#}; progress will not be saved.", e);
+                state = None;
+            }
+        }
+
+        if runtimes[idx].status == StepStatus::Failed && !step.optional {
+            break;
+        }
+    }
+
+    // `--run-step` reports on the steps it ran, not on the whole file.
+    let report: Vec<usize> = match &plan {
+        Some(plan) => plan.clone(),
+        None => (0..steps.len()).collect(),
+    };
+    for &idx in &report {
+        let rt = &mut runtimes[idx];
+        if rt.status == StepStatus::Pending {
+            rt.status = StepStatus::NotRun;
+            emit(idx, rt.status);
+        }
+    }
+    let report_steps: Vec<Step> = report.iter().map(|&i| steps[i].clone()).collect();
+    let runtimes: Vec<StepRuntime> = report.iter().map(|&i| runtimes[i].clone()).collect();
+    let durations: Vec<_> = report.iter().map(|&i| durations[i]).collect();
+
+    printer.summary(&report_steps, &runtimes, &durations);
+    if let Some(path) = &cli.report {
+        let aborted = timed_out.as_ref().map(|_| "global timeout reached");
+        report::write(path, &report_steps, &runtimes, &durations, aborted)?;
+    }
+    if cli.notify {
+        let body = notify::summary(&report_steps, &runtimes);
+        if let Some(note) = notify::send("wiza-rs: run finished", &body) {
+            eprint!("{}", note);
+        }
+    }
+    if let Some(e) = timed_out {
+        return Err(e);
+    }
+
+    // The step asked for with `--run-step` decides the exit status even if
+    // it is optional.
+    let failed = report_steps
+        .iter()
+        .zip(&runtimes)
+        .filter(|(step, rt)| {
+            rt.status == StepStatus::Failed && (!step.optional || plan.is_some())
+        })
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("{} step(s) failed", failed));
+    }
+    Ok(())
+}
+
+/// The steps `--run-step name` runs, in order: the step itself, after the
+/// steps it (transitively) depends on with `--with-deps`.
+fn plan_step(
+    steps: &[Step],
+    deps: &[Vec<usize>],
+    name: &str,
+    with_deps: bool,
+) -> Result<Vec<usize>> {
+    let target = steps
+        .iter()
+        .position(|step| step.name == name)
+        .ok_or_else(|| anyhow!("No step named '{}' (see --run-step)", name))?;
+    let mut plan = Vec::new();
+    if with_deps {
+        add_with_deps(target, deps, &mut plan);
+    } else {
+        plan.push(target);
+    }
+    Ok(plan)
+}
+
+/// Depth-first: `idx`'s dependencies, then `idx` itself, each once.
+fn add_with_deps(idx: usize, deps: &[Vec<usize>], plan: &mut Vec<usize>) {
+    if plan.contains(&idx) {
+        return;
+    }
+    for &dep in &deps[idx] {
+        add_with_deps(dep, deps, plan);
+    }
+    plan.push(idx);
+}
+
+/// Like `run_step`, but steps that normally collect input in the TUI do
+/// without it: prompts use existing vars or their defaults, app_selection
+/// follows `--apps` (skipped without it), and git_config is skipped.
+fn run_headless_step(
+    step: &Step,
+    rt: &mut StepRuntime,
+    ctx: &mut RunContext,
+    apps: Option<AppsPolicy>,
+) -> Result<()> {
+    match &step.kind {
+        StepKind::Prompt { params } => {
+            rt.log.push_str(&format!("== Running step: {} (prompt) ==\n", step.name));
+            let mut values = Vec::new();
+            for field in &params.fields {
+                let value = ctx.vars.get(&field.var).cloned().or_else(|| field.default.clone());
+                match value {
+                    Some(value) => values.push(value),
+                    None => {
+                        return Err(anyhow!(
+                            "No value for '{}' in headless mode; set it in vars or an env file",
+                            field.var
+                        ));
+                    }
+                }
+            }
+            apply_prompt(params, values, rt, ctx);
+            Ok(())
+        }
+        StepKind::AppSelection { params } if apps.is_some() => {
+            rt.log.push_str(&format!("== Running step: {} (app_selection) ==\n", step.name));
+            let (policy, selection): (&str, Vec<usize>) = match apps {
+                Some(AppsPolicy::All) => ("all", (0..params.apps.len()).collect()),
+                Some(AppsPolicy::Defaults) => (
+                    "defaults",
+                    (0..params.apps.len()).filter(|&i| params.apps[i].default).collect(),
+                ),
+                _ => ("none", Vec::new()),
+            };
+            rt.log.push_str(&format!("Selecting apps with --apps {}.\n", policy));
+            rt.status = StepStatus::Running;
+            apply_app_selection(params, &selection, step, rt, ctx)?;
+            if rt.status == StepStatus::Running {
+                rt.status = StepStatus::Success;
+            }
+            Ok(())
+        }
+        StepKind::GitConfig { .. } | StepKind::AppSelection { .. } => {
+            rt.log.push_str(&format!(
+                "== Skipping step: {} ({}) ==\n",
+                step.name,
+                step.kind.label()
+            ));
+            rt.log
+                .push_str("This step needs input from the TUI; skipped in headless mode.\n");
+            rt.status = StepStatus::Skipped;
+            Ok(())
+        }
+        _ => run_step(step, rt, ctx),
+    }
+}
+
+/// Writes step logs and the final summary in the chosen `--format`.
+struct Printer {
+    format: OutputFormat,
+    /// Colour the CI prefixes; only when stdout is a terminal and colour is
+    /// not turned off.
+    color: bool,
+}
+
+impl Printer {
+    fn new(format: OutputFormat, color: bool) -> Self {
+        Self {
+            format,
+            color: color && std::io::stdout().is_terminal(),
+        }
+    }
+
+    fn paint(&self, text: &str, status: StepStatus) -> String {
+        if !self.color {
+            return text.to_string();
+        }
+        let code = match status {
+            StepStatus::Success => "32",
+            StepStatus::Failed => "31",
+            StepStatus::Running => "33",
+            StepStatus::Skipped => "34",
+            StepStatus::Pending | StepStatus::NotRun => "90",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+
+    /// Print the log of a finished step (or of setup, under `name`).
+    fn block(&self, name: &str, status: StepStatus, log: &str) {
+        match self.format {
+            OutputFormat::Plain => {
+                print!("{}", log);
+                if !log.ends_with('\n') && !log.is_empty() {
+                    println!();
+                }
+            }
+            OutputFormat::Ci => {
+                let prefix = self.paint(&format!("[{}]", name), status);
+                for line in log.lines() {
+                    println!("{} {}", prefix, line);
+                }
+            }
+        }
+    }
+
+    fn summary(&self, steps: &[Step], runtimes: &[StepRuntime], durations: &[Option<Duration>]) {
+        match self.format {
+            OutputFormat::Plain => {
+                println!("\nResult: {}", notify::summary(steps, runtimes));
+            }
+            OutputFormat::Ci => {
+                let width = steps
+                    .iter()
+                    .map(|s| s.name.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(4);
+                println!();
+                println!("{:<width$}  {:<8}  {:>8}", "STEP", "STATUS", "TIME", width = width);
+                for ((step, rt), duration) in steps.iter().zip(runtimes).zip(durations) {
+                    let time = duration
+                        .map(|d| format!("{:.1}s", d.as_secs_f64()))
+                        .unwrap_or_else(|| "-".to_string());
+                    let status = format!("{:<8}", rt.status.label());
+                    println!(
+                        "{:<width$}  {}  {:>8}",
+                        step.name,
+                        self.paint(&status, rt.status),
+                        time,
+                        width = width
+                    );
+                }
+                println!("\n{}", notify::summary(steps, runtimes));
+            }
+        }
+    }
+}
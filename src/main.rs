@@ -1,26 +1,212 @@
+mod cli;
+mod clipboard;
+mod dotenv;
+mod events;
 mod executor;
+mod headless;
 mod model;
+mod notify;
+mod report;
+mod scheduler;
+mod sha256;
+mod spill;
+mod state;
+mod theme;
 mod tui;
 
-use anyhow::{Context, Result};
-use model::StepFile;
-use std::fs;
+use anyhow::{anyhow, Context, Result};
+use cli::{Cli, ConfigFormat};
+use executor::{GlobalTimeout, RunContext};
+use model::{Issue, StepFile};
+use std::time::Duration;
 
-fn main() -> Result<()> {
-    let yaml_path = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "steps.yaml".to_string());
+/// Exit code used when `--max-runtime` is exceeded (same as coreutils `timeout`).
+const EXIT_GLOBAL_TIMEOUT: i32 = 124;
 
-    let yaml_content =
-        fs::read_to_string(&yaml_path).with_context(|| format!("Failed to read {}", yaml_path))?;
+fn main() -> Result<()> {
+    let cli = Cli::parse()?;
+    if cli.help {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if cli.yaml_schema {
+        let schema = schemars::schema_for!(StepFile);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
+    if cli.check {
+        let ok = check(&cli)?;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if let Some(secs) = cli.max_runtime {
+        executor::set_max_runtime(Duration::from_secs(secs));
+    }
+    if let Some(secs) = cli.step_timeout_default {
+        executor::set_step_timeout_default(secs);
+    }
+    if let Some(max) = cli.max_line_len {
+        executor::set_max_line_len(max);
+    }
+    if cli.dry_run {
+        executor::set_dry_run();
+    }
+    if cli.verbose {
+        executor::set_verbose();
+    }
+    if let Some(path) = &cli.full_log {
+        executor::open_full_log(path)?;
+    }
+    let yaml_content = cli.read_steps_file()?;
 
-    let steps_file: StepFile =
-        serde_yaml::from_str(&yaml_content).context("Failed to parse YAML")?;
+    let steps_file = StepFile::parse(&yaml_content, &cli.yaml_path)?;
 
     // NEW: schema validation with friendly errors
     steps_file
         .validate()
         .context("YAML failed validation")?;
+    if cli.deny_warnings {
+        let warnings = steps_file.warnings(&cli.base_dir());
+        if !warnings.is_empty() {
+            let list: Vec<String> = warnings.iter().map(|w| format!("  - {}", w.message)).collect();
+            return Err(anyhow!(
+                "YAML has {} warning(s), denied by --deny-warnings:\n{}",
+                warnings.len(),
+                list.join("\n")
+            ));
+        }
+    }
+
+    match cli.print_config {
+        Some(ConfigFormat::Yaml) => {
+            print!("{}", steps_file.to_yaml()?);
+            return Ok(());
+        }
+        Some(ConfigFormat::Json) => {
+            println!("{}", steps_file.to_json()?);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if cli.strict {
+        executor::check_script_files(&steps_file, &cli.base_dir())
+            .context("YAML failed strict validation")?;
+    }
+    if cli.strict {
+        let vars = RunContext::prepare(&steps_file, &cli, &mut String::new())?.vars;
+        executor::check_vars(&steps_file, &vars, &cli.base_dir())
+            .context("YAML failed strict validation")?;
+    }
+    if cli.strict && !cli.no_syntax_check {
+        executor::check_syntax(&steps_file, &cli.base_dir())
+            .context("YAML failed strict validation")?;
+    }
+
+    if cli.reset_once {
+        if let Some(once) = state::OnceMarkers::for_file(&cli.yaml_path) {
+            let cleared = once.clear().context("Failed to reset run_once markers")?;
+            eprintln!("wiza-rs: forgot {} run_once step(s) (--reset-once)", cleared);
+        }
+    }
+
+    let res = if cli.headless {
+        headless::run(&steps_file, &cli)
+    } else {
+        tui::run_tui(&steps_file, &cli)
+    };
+    if let Err(e) = &res {
+        if e.is::<GlobalTimeout>() {
+            eprintln!("wiza-rs: {:#}", e);
+            std::process::exit(EXIT_GLOBAL_TIMEOUT);
+        }
+    }
+    res
+}
+
+/// One line of the `--check` report.
+#[derive(serde::Serialize)]
+struct CheckEntry {
+    severity: &'static str,
+    step: Option<String>,
+    message: String,
+}
+
+/// `--check`: validate the steps file and print every problem as a JSON array
+/// of `{severity, step, message}`. Returns whether there were no errors.
+fn check(cli: &Cli) -> Result<bool> {
+    let mut report = Vec::new();
+    let mut push = |severity: &'static str, step: Option<String>, message: String| {
+        report.push(CheckEntry {
+            severity,
+            step,
+            message,
+        });
+    };
+    let step_name = |steps_file: &StepFile, issue: &Issue| {
+        issue.step.map(|i| steps_file.steps[i].name.clone())
+    };
+
+    let mut ok = true;
+    let mut warned = false;
+    let parsed = cli
+        .read_steps_file()
+        .and_then(|yaml| StepFile::parse(&yaml, &cli.yaml_path));
+    match parsed {
+        Err(e) => {
+            push("error", None, format!("{:#}", e));
+            ok = false;
+        }
+        Ok(steps_file) => {
+            if let Err(errors) = steps_file.validate() {
+                for issue in &errors.0 {
+                    let step = step_name(&steps_file, issue);
+                    push("error", step, issue.message.clone());
+                }
+                ok = false;
+            }
+            for issue in &steps_file.warnings(&cli.base_dir()) {
+                let step = step_name(&steps_file, issue);
+                push("warning", step, issue.message.clone());
+                warned = true;
+            }
+            if ok && cli.strict {
+                if let Err(errors) = executor::check_script_files(&steps_file, &cli.base_dir()) {
+                    for issue in &errors.0 {
+                        let step = step_name(&steps_file, issue);
+                        push("error", step, issue.message.clone());
+                    }
+                    ok = false;
+                }
+            }
+            if ok && cli.strict {
+                let vars = RunContext::prepare(&steps_file, cli, &mut String::new())
+                    .map(|ctx| ctx.vars);
+                match vars {
+                    Err(e) => {
+                        push("error", None, format!("{:#}", e));
+                        ok = false;
+                    }
+                    Ok(vars) => {
+                        let res = executor::check_vars(&steps_file, &vars, &cli.base_dir());
+                        if let Err(errors) = res {
+                            for issue in &errors.0 {
+                                let step = step_name(&steps_file, issue);
+                                push("error", step, issue.message.clone());
+                            }
+                            ok = false;
+                        }
+                    }
+                }
+            }
+            if ok && cli.strict && !cli.no_syntax_check {
+                if let Err(e) = executor::check_syntax(&steps_file, &cli.base_dir()) {
+                    push("error", None, format!("{:#}", e));
+                    ok = false;
+                }
+            }
+        }
+    }
 
-    tui::run_tui(&steps_file.steps)
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(ok && !(warned && cli.deny_warnings))
 }
@@ -1,29 +1,213 @@
-use serde::Deserialize;
-use anyhow::{Result, anyhow};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result, anyhow};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 
-#[derive(Debug, Deserialize)]
+/// Variables available for `${var}` substitution in step commands.
+pub type Vars = HashMap<String, String>;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct StepFile {
+    /// Initial values for the global vars map; prompt steps add to it at runtime.
+    #[serde(default)]
+    pub vars: Vars,
+
+    /// Dotenv file (relative to the base dir) whose `KEY=VALUE` lines are
+    /// added to `vars`, overriding values of the same name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+
+    /// Optional overrides for status glyphs and colours.
+    #[serde(default)]
+    pub theme: ThemeConfig,
+
     pub steps: Vec<Step>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct ThemeConfig {
+    /// Base look; per-status overrides apply on top of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<ThemePreset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<StatusStyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running: Option<StatusStyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<StatusStyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<StatusStyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed: Option<StatusStyleConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_run: Option<StatusStyleConfig>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemePreset {
+    /// Unicode glyphs and colours for dark backgrounds, as shipped.
+    #[default]
+    #[serde(alias = "dark")]
+    Default,
+    /// Plain ASCII glyphs for terminals/fonts without Unicode.
+    Ascii,
+    /// Colours legible on light backgrounds.
+    Light,
+    /// No colours at all, like `--no-color`.
+    Mono,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct StatusStyleConfig {
+    /// Glyph shown in the steps list, e.g. `[ok]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Colour name (`red`, `lightblue`, ...) or `#rrggbb`, used in the status bar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct Step {
     pub name: String,
 
     #[serde(flatten)]
     pub kind: StepKind,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub pre_script: Option<String>,
 
-    #[serde(default)]
+    /// File whose contents run as `pre_script`, relative to the base dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_script_file: Option<String>,
+
+    /// A failing `pre_script` skips the step (the default); `false` makes
+    /// it fail the step instead, for pre_scripts that do real setup.
+    #[serde(default = "default_true")]
+    pub pre_script_is_gate: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub script: Option<String>,
 
-    #[serde(default)]
+    /// File whose contents run as `script`, relative to the base dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_file: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub post_script: Option<String>,
+
+    /// File whose contents run as `post_script`, relative to the base dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_script_file: Option<String>,
+
+    /// Hand the real terminal (stdin included) to this step's commands.
+    /// By default stdin is closed so commands waiting for input fail fast.
+    #[serde(default)]
+    pub interactive: bool,
+
+    /// Treat non-zero exits of this step's commands as warnings instead of failures.
+    #[serde(default)]
+    pub continue_on_error: bool,
+
+    /// Variable receiving the trimmed stdout of `script` when it succeeds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<String>,
+
+    /// Regex that the stdout or stderr of `script` must match for the step
+    /// to succeed, in addition to the exit code check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_if: Option<String>,
+
+    /// Exit code of `script` that counts as success, instead of 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_exit: Option<i32>,
+
+    /// Kill any command of this step that runs longer than this many
+    /// seconds; overrides `--step-timeout-default`, 0 = no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// Run this step's commands as another user via `sudo -u`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<String>,
+
+    /// Run the step again this many times when it fails.
+    #[serde(default)]
+    pub retries: u32,
+
+    /// A failure of this step does not stop the run-all batch.
+    #[serde(default)]
+    pub optional: bool,
+
+    /// Started with `Enter`, run on a worker thread so the TUI stays usable.
+    #[serde(default)]
+    pub background: bool,
+
+    /// Once this step has succeeded, skip it in every later run until
+    /// `--reset-once`.
+    #[serde(default)]
+    pub run_once: bool,
+
+    /// Names of steps that must succeed (or be skipped) before this one runs.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Step {
+    /// The three script fields with their `*_file` alternatives, as
+    /// (field name, inline text, file path).
+    pub fn script_fields(&self) -> [(&'static str, Option<&str>, Option<&str>); 3] {
+        [
+            ("pre_script", self.pre_script.as_deref(), self.pre_script_file.as_deref()),
+            ("script", self.script.as_deref(), self.script_file.as_deref()),
+            ("post_script", self.post_script.as_deref(), self.post_script_file.as_deref()),
+        ]
+    }
+
+    /// Whether running this step needs the TUI or the real terminal, so it
+    /// cannot run on a background worker.
+    pub fn needs_terminal(&self) -> bool {
+        self.interactive
+            || matches!(
+                self.kind,
+                StepKind::GitConfig { .. } | StepKind::AppSelection { .. } | StepKind::Prompt { .. }
+            )
+    }
+}
+
+/// For each step, the indices of the steps named in its `depends_on`.
+/// Names are resolved to the first step with that name; `validate` rejects
+/// unknown and ambiguous names.
+pub fn dependencies(steps: &[Step]) -> Vec<Vec<usize>> {
+    steps
+        .iter()
+        .map(|step| {
+            step.depends_on
+                .iter()
+                .filter_map(|name| steps.iter().position(|s| &s.name == name))
+                .collect()
+        })
+        .collect()
+}
+
+/// Whether every step in `deps` has succeeded or been skipped.
+pub fn dependencies_met(deps: &[usize], runtimes: &[StepRuntime]) -> bool {
+    deps.iter()
+        .all(|&dep| matches!(runtimes[dep].status, StepStatus::Success | StepStatus::Skipped))
+}
+
+/// First pending step, in file order, whose dependencies are all met.
+pub fn next_ready(deps: &[Vec<usize>], runtimes: &[StepRuntime]) -> Option<usize> {
+    (0..runtimes.len()).find(|&idx| {
+        runtimes[idx].status == StepStatus::Pending && dependencies_met(&deps[idx], runtimes)
+    })
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum StepKind {
     #[serde(rename = "script")]
@@ -32,11 +216,78 @@ pub enum StepKind {
     #[serde(rename = "add_text")]
     AddText { params: AddTextParams },
 
+    #[serde(rename = "replace_in_file")]
+    ReplaceInFile { params: ReplaceInFileParams },
+
     #[serde(rename = "git_config")]
     GitConfig { params: GitConfigParams },
 
+    #[serde(rename = "git_clone")]
+    GitClone { params: GitCloneParams },
+
+    #[serde(rename = "service")]
+    Service { params: ServiceParams },
+
     #[serde(rename = "app_selection")]
     AppSelection { params: AppSelectionParams },
+
+    #[serde(rename = "prompt")]
+    Prompt { params: PromptParams },
+
+    #[serde(rename = "symlink")]
+    Symlink { params: SymlinkParams },
+
+    #[serde(rename = "template")]
+    Template { params: TemplateParams },
+
+    #[serde(rename = "download")]
+    Download { params: DownloadParams },
+
+    #[serde(rename = "package")]
+    Package { params: PackageParams },
+
+    #[serde(rename = "line_in_file")]
+    LineInFile { params: LineInFileParams },
+}
+
+impl StepKind {
+    /// The `type` name used in YAML.
+    pub fn label(&self) -> &'static str {
+        match self {
+            StepKind::Script => "script",
+            StepKind::AddText { .. } => "add_text",
+            StepKind::ReplaceInFile { .. } => "replace_in_file",
+            StepKind::GitConfig { .. } => "git_config",
+            StepKind::GitClone { .. } => "git_clone",
+            StepKind::Service { .. } => "service",
+            StepKind::AppSelection { .. } => "app_selection",
+            StepKind::Prompt { .. } => "prompt",
+            StepKind::Symlink { .. } => "symlink",
+            StepKind::Template { .. } => "template",
+            StepKind::Download { .. } => "download",
+            StepKind::Package { .. } => "package",
+            StepKind::LineInFile { .. } => "line_in_file",
+        }
+    }
+
+    /// Short tag shown before the step name in the steps list. Kinds that
+    /// do the same sort of thing share one.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            StepKind::Script => "sh",
+            StepKind::AddText { .. }
+            | StepKind::ReplaceInFile { .. }
+            | StepKind::Symlink { .. }
+            | StepKind::Template { .. }
+            | StepKind::LineInFile { .. } => "edit",
+            StepKind::GitConfig { .. } | StepKind::GitClone { .. } => "git",
+            StepKind::Download { .. } => "get",
+            StepKind::Service { .. } => "svc",
+            StepKind::AppSelection { .. } => "apps",
+            StepKind::Package { .. } => "pkg",
+            StepKind::Prompt { .. } => "ask",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
@@ -47,109 +298,1268 @@ pub enum StepStatus {
     Skipped,
     Success,
     Failed,
+    /// Never reached before the run ended (unlike `Skipped`, which is a decision).
+    NotRun,
+}
+
+impl StepStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StepStatus::Pending => "Pending",
+            StepStatus::Running => "Running",
+            StepStatus::Skipped => "Skipped",
+            StepStatus::Success => "Success",
+            StepStatus::Failed => "Failed",
+            StepStatus::NotRun => "Not run",
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct StepRuntime {
     pub status: StepStatus,
     pub log: String,
+    /// Structured record of every command run for this step, in order.
+    pub commands: Vec<CommandRecord>,
+    /// Command running right now. Clones share it, so the TUI sees what a
+    /// batch worker is doing.
+    pub current_command: Arc<Mutex<Option<String>>>,
+    /// The log as of the latest command started, followed by that command's
+    /// output as it arrives. Clones share it, so the TUI can show a batch or
+    /// background worker's log while the step runs.
+    pub live_log: Arc<Mutex<String>>,
+    /// `status` was set by hand (`s`, `m`) rather than by running the step, so
+    /// pressing the key again may undo it.
+    pub set_by_hand: bool,
+    /// Attempt the latest run is on (or ended at), counting `retries`.
+    /// Clones share it, so the TUI sees a batch worker retry.
+    pub attempts: Arc<AtomicU32>,
+    /// 1 + the step's `retries`, set when the step runs.
+    pub max_attempts: u32,
+    /// How far an app_selection step's installs are. Clones share it, so the
+    /// TUI can draw a gauge while a worker installs.
+    pub installs: Arc<Mutex<InstallProgress>>,
+}
+
+/// Installs of an app_selection step: `done` of `total` finished, `failed`
+/// of them unsuccessfully.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InstallProgress {
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl StepRuntime {
+    pub fn set_current_command(&self, cmd: Option<&str>) {
+        if let Ok(mut current) = self.current_command.lock() {
+            *current = cmd.map(str::to_string);
+        }
+        self.publish_log(cmd);
+    }
+
+    /// Copy `log` to `live_log`, with a `$ cmd` line for a command about to start.
+    pub fn publish_log(&self, cmd: Option<&str>) {
+        if let Ok(mut live) = self.live_log.lock() {
+            live.clone_from(&self.log);
+            if let Some(cmd) = cmd {
+                live.push_str(&format!("\n$ {}\n", cmd));
+            }
+        }
+    }
+
+    pub fn live_log(&self) -> String {
+        self.live_log.lock().map(|live| live.clone()).unwrap_or_default()
+    }
+
+    pub fn current_command(&self) -> Option<String> {
+        self.current_command.lock().ok().and_then(|current| current.clone())
+    }
+
+    pub fn update_installs(&self, update: impl FnOnce(&mut InstallProgress)) {
+        if let Ok(mut installs) = self.installs.lock() {
+            update(&mut installs);
+        }
+    }
+
+    pub fn installs(&self) -> InstallProgress {
+        self.installs.lock().map(|installs| *installs).unwrap_or_default()
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts.load(Ordering::SeqCst)
+    }
+
+    /// `(attempt 2/3)` once a step with `retries` has needed more than one
+    /// attempt; empty otherwise.
+    pub fn attempt_note(&self) -> String {
+        match self.attempts() {
+            attempt if attempt > 1 => format!("(attempt {}/{})", attempt, self.max_attempts),
+            _ => String::new(),
+        }
+    }
+
+    /// The most recent command that exited non-zero, if any.
+    pub fn last_failed_command(&self) -> Option<&CommandRecord> {
+        self.commands.iter().rev().find(|c| c.exit_code != 0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandRecord {
+    pub command: String,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct AddTextParams {
     pub file: String,
     pub content: String,
+    /// Where to put the content; appends when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    /// Append instead of failing when a pattern position finds no match.
+    #[serde(default)]
+    pub fallback_append: bool,
+    /// When appending, end the content with a newline (default `true`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailing_newline: Option<bool>,
+    /// When appending to a file that does not end with a newline, add one
+    /// first so the content starts on its own line.
+    #[serde(default)]
+    pub leading_newline: bool,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct ReplaceInFileParams {
+    pub file: String,
+    /// Text to look for; a regular expression when `regex` is set.
+    pub find: String,
+    /// Replacement text. With `regex`, `$1` / `${name}` refer to capture groups.
+    pub replace: String,
+    #[serde(default)]
+    pub regex: bool,
+    /// Replace at most this many matches (first ones first); all when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// Fail the step when nothing matches.
+    #[serde(default)]
+    pub require_match: bool,
+    /// Write the file through `sudo`, for files the user cannot write
+    /// (e.g. in `/etc`).
+    #[serde(default)]
+    pub sudo: bool,
+}
+
+/// Makes sure one line is (or is not) in a file, however often it runs.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct LineInFileParams {
+    pub file: String,
+    /// The line that should be there; required unless `state` is `absent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+    /// Regular expression for the line to replace (the last match) or, with
+    /// `state: absent`, the lines to remove. Without it only lines equal to
+    /// `line` count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub state: LineState,
+    /// Where to add the line when nothing matches; appends when omitted, or
+    /// when the pattern is not found either.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LineState {
+    #[default]
+    Present,
+    Absent,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct GitCloneParams {
+    pub url: String,
+    /// Directory to clone into.
+    pub dest: String,
+    /// Branch or tag to check out instead of the remote's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Shallow clone with this many commits of history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    /// When `dest` is already a checkout, `git pull` it instead of leaving it alone.
+    #[serde(default)]
+    pub update: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct DownloadParams {
+    pub url: String,
+    /// File to save the download as.
+    pub dest: String,
+    /// Expected SHA-256 of the file, in hex; the step fails on a mismatch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Permissions of the file as octal digits; `644` when omitted, so use
+    /// `755` for a binary or an AppImage.
+    #[serde(
+        default,
+        deserialize_with = "mode_digits",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mode: Option<String>,
+    /// Replace a file or symlink already at `dest`.
+    #[serde(default)]
+    pub force: bool,
+}
+
+impl DownloadParams {
+    pub fn mode(&self) -> &str {
+        self.mode.as_deref().unwrap_or("644")
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct SymlinkParams {
+    pub links: Vec<Link>,
+    /// Replace a file or symlink already at a target.
+    #[serde(default)]
+    pub force: bool,
+    /// Move whatever is at a target aside to `<target>.bak` first; wins over
+    /// `force`, and also works for directories.
+    #[serde(default)]
+    pub backup: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct Link {
+    /// What the link points to, relative to the base dir.
+    pub source: String,
+    /// Where the link is created, e.g. `~/.config/nvim`.
+    pub target: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct TemplateParams {
+    /// Template file, relative to the base dir; its `${var}` references are
+    /// filled in.
+    pub source: String,
+    /// File to write the rendered text to.
+    pub dest: String,
+    /// Extra vars for this template only, e.g. per-host values; they win
+    /// over the global vars of the same name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: Vars,
+    /// Overwrite a `dest` that exists with different contents.
+    #[serde(default)]
+    pub force: bool,
+    /// Permissions of `dest` as octal digits, e.g. `600`; left to the umask
+    /// when omitted.
+    #[serde(
+        default,
+        deserialize_with = "mode_digits",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct ServiceParams {
+    /// Unit name, e.g. `docker` or `syncthing.service`.
+    pub name: String,
+    pub action: ServiceAction,
+    /// A `systemctl --user` unit; system units go through `sudo`.
+    #[serde(default)]
+    pub user: bool,
+    /// Unit file (relative to the base dir) to install as `name` first,
+    /// followed by a `daemon-reload`; skipped when the installed copy is
+    /// the same.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit_file: Option<String>,
+    /// Log `systemctl status` after the action. Only informational: an
+    /// inactive unit does not fail the step.
+    #[serde(default)]
+    pub status: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceAction {
+    Enable,
+    Start,
+    Restart,
+    Stop,
+    /// `enable --now`: enable and start in one go.
+    EnableNow,
+}
+
+impl ServiceAction {
+    /// The `systemctl` arguments for this action.
+    pub fn args(self) -> &'static str {
+        match self {
+            ServiceAction::Enable => "enable",
+            ServiceAction::Start => "start",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Stop => "stop",
+            ServiceAction::EnableNow => "enable --now",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct PackageParams {
+    /// Packages to install with whichever manager the system has.
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Package lists to use instead of `packages` for some managers, for
+    /// packages named differently there (e.g. `fd-find` vs `fd`).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub overrides: HashMap<PackageManager, Vec<String>>,
+    /// Use this manager instead of detecting one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manager: Option<PackageManager>,
+    /// Refresh the package index before installing.
+    #[serde(default)]
+    pub update: bool,
+}
+
+impl PackageParams {
+    /// The packages to install with `manager`.
+    pub fn packages_for(&self, manager: PackageManager) -> &[String] {
+        self.overrides.get(&manager).unwrap_or(&self.packages)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+    Zypper,
+    Brew,
+}
+
+impl PackageManager {
+    pub const ALL: [PackageManager; 5] = [
+        PackageManager::Apt,
+        PackageManager::Dnf,
+        PackageManager::Pacman,
+        PackageManager::Zypper,
+        PackageManager::Brew,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Zypper => "zypper",
+            PackageManager::Brew => "brew",
+        }
+    }
+
+    /// The program that has to be on PATH for this manager.
+    pub fn program(self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt-get",
+            other => other.label(),
+        }
+    }
+}
+
+/// Written in YAML as `append`, `prepend`, `{ before_pattern: "..." }`
+/// or `{ after_pattern: "..." }`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(from = "PositionSpec", into = "PositionSpec")]
+pub enum Position {
+    Append,
+    Prepend,
+    /// Insert before the first line containing the pattern.
+    BeforePattern(String),
+    /// Insert after the first line containing the pattern.
+    AfterPattern(String),
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(
+    untagged,
+    expecting = "`append`, `prepend`, `before_pattern: ...` or `after_pattern: ...`"
+)]
+enum PositionSpec {
+    Keyword(PositionKeyword),
+    BeforePattern { before_pattern: String },
+    AfterPattern { after_pattern: String },
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum PositionKeyword {
+    Append,
+    Prepend,
+}
+
+// The schema describes the YAML shape, i.e. `PositionSpec`.
+impl JsonSchema for Position {
+    fn schema_name() -> String {
+        "Position".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        PositionSpec::json_schema(gen)
+    }
+}
+
+impl From<Position> for PositionSpec {
+    fn from(position: Position) -> Self {
+        match position {
+            Position::Append => PositionSpec::Keyword(PositionKeyword::Append),
+            Position::Prepend => PositionSpec::Keyword(PositionKeyword::Prepend),
+            Position::BeforePattern(before_pattern) => PositionSpec::BeforePattern { before_pattern },
+            Position::AfterPattern(after_pattern) => PositionSpec::AfterPattern { after_pattern },
+        }
+    }
+}
+
+impl From<PositionSpec> for Position {
+    fn from(spec: PositionSpec) -> Self {
+        match spec {
+            PositionSpec::Keyword(PositionKeyword::Append) => Position::Append,
+            PositionSpec::Keyword(PositionKeyword::Prepend) => Position::Prepend,
+            PositionSpec::BeforePattern { before_pattern } => Position::BeforePattern(before_pattern),
+            PositionSpec::AfterPattern { after_pattern } => Position::AfterPattern(after_pattern),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct GitConfigParams {
     #[serde(default = "default_editor")]
     pub default_editor: String,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn default_editor() -> String {
     "vim".into()
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// A `mode` param, quoted or not: YAML reads a bare `644` as a number, whose
+/// digits are kept as they are written.
+fn mode_digits<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Mode {
+        Text(String),
+        Number(u64),
+    }
+    Ok(Option::<Mode>::deserialize(deserializer)?.map(|mode| match mode {
+        Mode::Text(text) => text,
+        Mode::Number(number) => number.to_string(),
+    }))
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct AppSelectionParams {
+    #[serde(default)]
     pub apps: Vec<AppDefinition>,
+    /// YAML list of more apps, relative to the steps file's directory. An
+    /// inline app with the same name replaces the catalog's entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apps_file: Option<String>,
+    /// Install command shared by all apps; `{pkg}` is replaced with each
+    /// app's `package` (or `name`). Used for apps without their own `install`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_template: Option<String>,
+    /// Install in file order (default) or in the order the apps were ticked.
+    #[serde(default)]
+    pub install_order: InstallOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallOrder {
+    /// The order the apps are listed in.
+    #[default]
+    Declaration,
+    /// The order you selected them in.
+    Selection,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl AppSelectionParams {
+    /// Merge the apps of `apps_file` (resolved against `dir`) into `apps`:
+    /// the catalog's apps first, each replaced by an inline app of the same
+    /// name, then the remaining inline apps. `apps_file` is cleared so the
+    /// merged list is what `--print-config` shows.
+    fn load_apps_file(&mut self, dir: &Path) -> Result<()> {
+        let Some(apps_file) = self.apps_file.take() else {
+            return Ok(());
+        };
+        let path = dir.join(&apps_file);
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read apps_file {}", path.display()))?;
+        let catalog: Vec<AppDefinition> = serde_yaml::from_str(&text)
+            .with_context(|| format!("Failed to parse apps_file {}", path.display()))?;
+        let mut inline = std::mem::take(&mut self.apps);
+        for app in catalog {
+            match inline.iter().position(|own| own.name == app.name) {
+                Some(i) => self.apps.push(inline.remove(i)),
+                None => self.apps.push(app),
+            }
+        }
+        self.apps.append(&mut inline);
+        Ok(())
+    }
+
+    /// The command that installs `app`: its own `install`, else the template.
+    pub fn install_command(&self, app: &AppDefinition) -> Option<String> {
+        if let Some(install) = &app.install {
+            return Some(install.clone());
+        }
+        let package = app.package.as_deref().unwrap_or(&app.name);
+        self.command_template
+            .as_ref()
+            .map(|template| template.replace("{pkg}", package))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
 pub struct AppDefinition {
     pub name: String,
     pub version: String,
     /// Command used to install this app (apt or custom script).
-    pub install: String,
+    /// Optional when the step has a `command_template`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install: Option<String>,
+    /// Package name substituted for `{pkg}` in `command_template`; defaults to `name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Freeform note shown in the selection list, e.g. a size or description.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<String>,
+    /// Command whose first line of output (e.g. a package size) is shown next
+    /// to the app. Run when the cursor first reaches the app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_command: Option<String>,
+    /// Ticked when the checklist opens, and installed by `--apps defaults`.
+    #[serde(default)]
+    pub default: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct PromptParams {
+    pub fields: Vec<PromptField>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+pub struct PromptField {
+    /// Label shown next to the input in the UI.
+    pub label: String,
+    /// Name of the variable that receives the entered value.
+    pub var: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Mask the input in the UI and redact the value from logs.
+    #[serde(default)]
+    pub secret: bool,
 }
 
 // ------------------ NEW: validation helpers ------------------
 
+/// One validation error or warning, tied to the step it was found in when
+/// there is one.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    /// Index into `StepFile::steps`.
+    pub step: Option<usize>,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(step: Option<usize>, message: String) -> Self {
+        Self { step, message }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Every problem `StepFile::validate` found, in file order.
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<Issue>);
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.as_slice() {
+            [single] => write!(f, "{}", single),
+            errors => {
+                write!(f, "{} problems found:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
 impl StepFile {
-    pub fn validate(&self) -> Result<()> {
+    /// Parse the steps file at `yaml_path` from its `text`, loading the
+    /// `apps_file` catalogs of app_selection steps.
+    pub fn parse(text: &str, yaml_path: &str) -> Result<Self> {
+        let mut file: StepFile = serde_yaml::from_str(text).context("Failed to parse YAML")?;
+        let dir = match Path::new(yaml_path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        for step in &mut file.steps {
+            if let StepKind::AppSelection { params } = &mut step.kind {
+                params
+                    .load_apps_file(dir)
+                    .with_context(|| format!("Step '{}' (app_selection)", step.name))?;
+            }
+        }
+        Ok(file)
+    }
+
+    /// The file as YAML, with defaults filled in and comments dropped.
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// The file as pretty-printed JSON; parses back like the YAML form.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Check the file for problems, reporting all of them at once.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
         if self.steps.is_empty() {
-            return Err(anyhow!("YAML must contain at least one step."));
+            let error = "YAML must contain at least one step.".to_string();
+            return Err(ValidationErrors(vec![Issue::new(None, error)]));
         }
+        let mut errors = Vec::new();
 
         for (i, step) in self.steps.iter().enumerate() {
+            let mut problems = Vec::new();
             if step.name.trim().is_empty() {
-                return Err(anyhow!("Step {} has an empty name.", i));
+                problems.push(format!("Step {} has an empty name.", i));
+            }
+
+            if let Some(user) = &step.run_as {
+                if user.trim().is_empty() {
+                    problems.push(format!("Step '{}' has an empty 'run_as' user.", step.name));
+                }
+            }
+
+            if let Some(var) = &step.capture {
+                if var.trim().is_empty() {
+                    problems.push(format!("Step '{}' has an empty 'capture' var name.", step.name));
+                }
+            }
+
+            if let Some(pattern) = &step.success_if {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    problems.push(format!(
+                        "Step '{}' has an invalid regex in 'success_if': {}",
+                        step.name,
+                        e
+                    ));
+                }
+                if step.interactive {
+                    problems.push(format!(
+                        "Step '{}' sets 'success_if' but is interactive, so its output \
+                         is not captured.",
+                        step.name
+                    ));
+                }
+            }
+            if (step.success_if.is_some() || step.expect_exit.is_some())
+                && !matches!(step.kind, StepKind::Script)
+            {
+                problems.push(format!(
+                    "Step '{}' sets 'success_if'/'expect_exit', which only apply to script steps.",
+                    step.name
+                ));
+            }
+            let asks = matches!(
+                step.kind,
+                StepKind::GitConfig { .. } | StepKind::AppSelection { .. } | StepKind::Prompt { .. }
+            );
+            if step.run_once && asks {
+                problems.push(format!(
+                    "Step '{}' ({}) sets 'run_once', which only steps that run without \
+                     asking support.",
+                    step.name,
+                    step.kind.label()
+                ));
+            }
+            if step.background && step.needs_terminal() {
+                problems.push(format!(
+                    "Step '{}' sets 'background' but needs the terminal (interactive or \
+                     asks for input), so it cannot run in the background.",
+                    step.name
+                ));
+            }
+
+            for (field, inline, file) in step.script_fields() {
+                if inline.is_some() && file.is_some() {
+                    problems.push(format!(
+                        "Step '{}' sets both '{}' and '{}_file'; use one of them.",
+                        step.name,
+                        field,
+                        field
+                    ));
+                }
+                if file.is_some_and(|file| file.trim().is_empty()) {
+                    problems.push(format!("Step '{}' has an empty '{}_file'.", step.name, field));
+                }
+            }
+
+            for dep in &step.depends_on {
+                if dep == &step.name {
+                    problems.push(format!("Step '{}' depends on itself.", step.name));
+                }
+                match self.steps.iter().filter(|s| &s.name == dep).count() {
+                    0 => {
+                        problems.push(format!(
+                            "Step '{}' depends on unknown step '{}'.",
+                            step.name,
+                            dep
+                        ));
+                    }
+                    1 => {}
+                    _ => {
+                        problems.push(format!(
+                            "Step '{}' depends on '{}', but that name is used by more than one step.",
+                            step.name,
+                            dep
+                        ));
+                    }
+                }
             }
 
             match &step.kind {
                 StepKind::Script => {
                     // Optional: enforce script presence if you want
-                    if step.script.is_none() {
-                        return Err(anyhow!(
-                            "Step '{}' (script) is missing 'script' field.",
+                    if step.script.is_none() && step.script_file.is_none() {
+                        problems.push(format!(
+                            "Step '{}' (script) is missing 'script' (or 'script_file') field.",
                             step.name
                         ));
                     }
                 }
                 StepKind::AddText { params } => {
                     if params.file.trim().is_empty() {
-                        return Err(anyhow!(
+                        problems.push(format!(
                             "Step '{}' (add_text) has empty 'file' param.",
                             step.name
                         ));
                     }
                     if params.content.is_empty() {
-                        return Err(anyhow!(
+                        problems.push(format!(
                             "Step '{}' (add_text) has empty 'content' param.",
                             step.name
                         ));
                     }
+                    if let Some(Position::BeforePattern(pattern) | Position::AfterPattern(pattern)) =
+                        &params.position
+                    {
+                        if pattern.is_empty() {
+                            problems.push(format!(
+                                "Step '{}' (add_text) has an empty pattern in 'position'.",
+                                step.name
+                            ));
+                        }
+                    }
+                }
+                StepKind::ReplaceInFile { params } => {
+                    if params.file.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (replace_in_file) has empty 'file' param.",
+                            step.name
+                        ));
+                    }
+                    if params.find.is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (replace_in_file) has empty 'find' param.",
+                            step.name
+                        ));
+                    }
+                    if params.regex {
+                        if let Err(e) = regex::Regex::new(&params.find) {
+                            problems.push(format!(
+                                "Step '{}' (replace_in_file) has an invalid regex in 'find': {}",
+                                step.name,
+                                e
+                            ));
+                        }
+                    }
+                }
+                StepKind::LineInFile { params } => {
+                    if params.file.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (line_in_file) has empty 'file' param.",
+                            step.name
+                        ));
+                    }
+                    match (&params.line, params.state) {
+                        (None, LineState::Present) => problems.push(format!(
+                            "Step '{}' (line_in_file) needs a 'line' param.",
+                            step.name
+                        )),
+                        (None, LineState::Absent) if params.regex.is_none() => {
+                            problems.push(format!(
+                                "Step '{}' (line_in_file) needs 'line' or 'regex' to know \
+                                 what to remove.",
+                                step.name
+                            ))
+                        }
+                        (Some(line), _) if line.contains('\n') => problems.push(format!(
+                            "Step '{}' (line_in_file) 'line' must be a single line.",
+                            step.name
+                        )),
+                        _ => {}
+                    }
+                    if let Some(Err(e)) = params.regex.as_deref().map(regex::Regex::new) {
+                        problems.push(format!(
+                            "Step '{}' (line_in_file) has an invalid 'regex': {}",
+                            step.name, e
+                        ));
+                    }
+                }
+                StepKind::GitClone { params } => {
+                    if params.url.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (git_clone) has empty 'url' param.",
+                            step.name
+                        ));
+                    }
+                    if params.dest.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (git_clone) has empty 'dest' param.",
+                            step.name
+                        ));
+                    }
+                    if params.depth == Some(0) {
+                        problems.push(format!(
+                            "Step '{}' (git_clone) has 'depth: 0'; use at least 1 or leave it out.",
+                            step.name
+                        ));
+                    }
+                }
+                StepKind::Service { params } => {
+                    if params.name.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (service) has empty 'name' param.",
+                            step.name
+                        ));
+                    }
+                    if params.unit_file.as_deref().is_some_and(|file| file.trim().is_empty()) {
+                        problems.push(format!(
+                            "Step '{}' (service) has empty 'unit_file' param.",
+                            step.name
+                        ));
+                    }
+                }
+                StepKind::Package { params } => {
+                    if params.packages.is_empty() && params.overrides.is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (package) must list at least one package.",
+                            step.name
+                        ));
+                    }
+                    let lists = std::iter::once(&params.packages).chain(params.overrides.values());
+                    if lists.flatten().any(|name| name.trim().is_empty()) {
+                        problems.push(format!(
+                            "Step '{}' (package) has an empty package name.",
+                            step.name
+                        ));
+                    }
+                }
+                StepKind::Download { params } => {
+                    if params.url.trim().is_empty() || params.dest.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (download) needs both 'url' and 'dest' params.",
+                            step.name
+                        ));
+                    }
+                    let hex = params.sha256.as_deref().map(str::trim);
+                    if hex.is_some_and(|hex| {
+                        hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit())
+                    }) {
+                        problems.push(format!(
+                            "Step '{}' (download) 'sha256' must be 64 hex digits.",
+                            step.name
+                        ));
+                    }
+                    if let Some(Err(e)) = params.mode.as_deref().map(parse_mode) {
+                        problems.push(format!("Step '{}' (download): {}", step.name, e));
+                    }
+                }
+                StepKind::Template { params } => {
+                    if params.source.trim().is_empty() || params.dest.trim().is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (template) needs both 'source' and 'dest' params.",
+                            step.name
+                        ));
+                    }
+                    if let Some(Err(e)) = params.mode.as_deref().map(parse_mode) {
+                        problems.push(format!("Step '{}' (template): {}", step.name, e));
+                    }
+                }
+                StepKind::Symlink { params } => {
+                    if params.links.is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (symlink) must have at least one link.",
+                            step.name
+                        ));
+                    }
+                    for (n, link) in params.links.iter().enumerate() {
+                        if link.source.trim().is_empty() || link.target.trim().is_empty() {
+                            problems.push(format!(
+                                "Step '{}' (symlink) link {} needs both 'source' and 'target'.",
+                                step.name,
+                                n + 1
+                            ));
+                        }
+                    }
                 }
                 StepKind::GitConfig { params: _ } => {
                     // Nothing mandatory besides defaults; you could check default_editor if you want.
                 }
                 StepKind::AppSelection { params } => {
                     if params.apps.is_empty() {
-                        return Err(anyhow!(
-                            "Step '{}' (app_selection) must have at least one app.",
+                        problems.push(format!(
+                            "Step '{}' (app_selection) must have at least one app (in 'apps' \
+                             or 'apps_file').",
                             step.name
                         ));
                     }
                     for app in &params.apps {
                         if app.name.trim().is_empty() {
-                            return Err(anyhow!(
+                            problems.push(format!(
                                 "Step '{}' (app_selection) has an app with empty name.",
                                 step.name
                             ));
                         }
-                        if app.install.trim().is_empty() {
-                            return Err(anyhow!(
-                                "Step '{}' (app_selection) app '{}' has empty install command.",
+                        let install = params.install_command(app).unwrap_or_default();
+                        if install.trim().is_empty() {
+                            problems.push(format!(
+                                "Step '{}' (app_selection) app '{}' needs an 'install' command \
+                                 or the step needs a 'command_template'.",
                                 step.name,
                                 app.name
                             ));
                         }
                     }
                 }
+                StepKind::Prompt { params } => {
+                    if params.fields.is_empty() {
+                        problems.push(format!(
+                            "Step '{}' (prompt) must have at least one field.",
+                            step.name
+                        ));
+                    }
+                    let mut seen = HashSet::new();
+                    for field in &params.fields {
+                        if field.label.trim().is_empty() || field.var.trim().is_empty() {
+                            problems.push(format!(
+                                "Step '{}' (prompt) has a field with empty label or var name.",
+                                step.name
+                            ));
+                        }
+                        if !seen.insert(field.var.as_str()) {
+                            problems.push(format!(
+                                "Step '{}' (prompt) declares var '{}' more than once.",
+                                step.name,
+                                field.var
+                            ));
+                        }
+                    }
+                }
             }
+            errors.extend(problems.into_iter().map(|message| Issue::new(Some(i), message)));
         }
 
-        Ok(())
+        if let Some(cycle) = dependency_cycle(&dependencies(&self.steps)) {
+            let names: Vec<&str> = cycle.iter().map(|&i| self.steps[i].name.as_str()).collect();
+            let message = format!("Steps depend on each other in a cycle: {}", names.join(" -> "));
+            errors.push(Issue::new(cycle.first().copied(), message));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(errors))
+        }
+    }
+
+    /// Non-fatal problems worth showing before the run starts. Relative
+    /// paths in params are checked against `base_dir`.
+    pub fn warnings(&self, base_dir: &Path) -> Vec<Issue> {
+        // Paths with vars in them are only known at run time.
+        let local_path = |path: &str| {
+            let resolved = || PathBuf::from(expand_home(&resolve_against(base_dir, path)));
+            (!path.contains("${")).then(resolved)
+        };
+        let mut warnings = Vec::new();
+        let mut names = HashSet::new();
+
+        for (i, step) in self.steps.iter().enumerate() {
+            let mut problems = Vec::new();
+            if !names.insert(step.name.as_str()) {
+                problems.push(format!("Step name '{}' is used more than once.", step.name));
+            }
+            let has_script = step.script.is_some() || step.script_file.is_some();
+            if has_script && !matches!(step.kind, StepKind::Script) {
+                problems.push(format!(
+                    "Step '{}' ({}) has a 'script' field, which only script steps use.",
+                    step.name,
+                    step.kind.label()
+                ));
+            }
+            if step.capture.is_some() && !matches!(step.kind, StepKind::Script) {
+                problems.push(format!(
+                    "Step '{}' ({}) sets 'capture', which only script steps support.",
+                    step.name,
+                    step.kind.label()
+                ));
+            }
+            if let StepKind::AddText { params } = &step.kind {
+                let appends = matches!(params.position, None | Some(Position::Append));
+                if !appends && (params.trailing_newline.is_some() || params.leading_newline) {
+                    problems.push(format!(
+                        "Step '{}' (add_text) sets 'trailing_newline'/'leading_newline', \
+                         which only apply when appending.",
+                        step.name
+                    ));
+                }
+            }
+            let has_pre_script = step.pre_script.is_some() || step.pre_script_file.is_some();
+            if has_pre_script && step.pre_script_is_gate && step.continue_on_error {
+                problems.push(format!(
+                    "Step '{}' sets 'continue_on_error', so a failing pre_script never skips it; \
+                     set 'pre_script_is_gate: false' to say that is intended.",
+                    step.name
+                ));
+            }
+            if !has_pre_script && !step.pre_script_is_gate {
+                problems.push(format!(
+                    "Step '{}' sets 'pre_script_is_gate' but has no pre_script.",
+                    step.name
+                ));
+            }
+            if let StepKind::Download { params } = &step.kind {
+                if params.sha256.is_none() {
+                    problems.push(format!(
+                        "Step '{}' (download) has no 'sha256', so the file is not verified.",
+                        step.name
+                    ));
+                }
+            }
+            let edited = match &step.kind {
+                StepKind::AddText { params } => Some(&params.file),
+                StepKind::LineInFile { params } => Some(&params.file),
+                _ => None,
+            };
+            let parent = edited.and_then(|file| local_path(file)).and_then(|path| {
+                path.parent().filter(|dir| !dir.is_dir()).map(Path::to_path_buf)
+            });
+            if let Some(dir) = parent {
+                problems.push(format!(
+                    "Step '{}' ({}) writes into {}, which does not exist; the step fails \
+                     unless an earlier one creates it.",
+                    step.name,
+                    step.kind.label(),
+                    dir.display()
+                ));
+            }
+            if let StepKind::Symlink { params } = &step.kind {
+                for link in &params.links {
+                    let Some(source) = local_path(&link.source) else { continue };
+                    if std::fs::symlink_metadata(&source).is_err() {
+                        problems.push(format!(
+                            "Step '{}' (symlink) links to {}, which does not exist; the link \
+                             fails unless an earlier step creates it.",
+                            step.name,
+                            source.display()
+                        ));
+                    }
+                }
+            }
+            if let StepKind::GitConfig { params } = &step.kind {
+                if let Some(problem) = missing_editor(&params.default_editor) {
+                    problems.push(format!("Step '{}' (git_config): {}", step.name, problem));
+                }
+            }
+            if step.capture.is_some() && step.interactive {
+                problems.push(format!(
+                    "Step '{}' is interactive, so its output cannot be captured.",
+                    step.name
+                ));
+            }
+            warnings.extend(problems.into_iter().map(|message| Issue::new(Some(i), message)));
+        }
+
+        if self.steps.iter().any(|s| s.run_as.is_some()) && !on_path("sudo") {
+            let message = "Some steps use 'run_as' but sudo was not found on PATH.".to_string();
+            warnings.push(Issue::new(None, message));
+        }
+
+        warnings
+    }
+}
+
+/// A dependency cycle as a list of step indices (first repeated at the end),
+/// if there is one.
+fn dependency_cycle(deps: &[Vec<usize>]) -> Option<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        idx: usize,
+        deps: &[Vec<usize>],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<usize>> {
+        marks[idx] = Mark::Visiting;
+        path.push(idx);
+        for &dep in &deps[idx] {
+            match marks[dep] {
+                Mark::Visiting => {
+                    let start = path.iter().position(|&i| i == dep).unwrap_or(0);
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(dep);
+                    return Some(cycle);
+                }
+                Mark::New => {
+                    if let Some(cycle) = visit(dep, deps, marks, path) {
+                        return Some(cycle);
+                    }
+                }
+                Mark::Done => {}
+            }
+        }
+        path.pop();
+        marks[idx] = Mark::Done;
+        None
+    }
+
+    let mut marks = vec![Mark::New; deps.len()];
+    for idx in 0..deps.len() {
+        if marks[idx] == Mark::New {
+            if let Some(cycle) = visit(idx, deps, &mut marks, &mut Vec::new()) {
+                return Some(cycle);
+            }
+        }
+    }
+    None
+}
+
+/// Parse a file mode written as 3 or 4 octal digits (`755`, `0644`).
+/// Used by validation and execution alike so both report the same error.
+pub fn parse_mode(mode: &str) -> Result<u32> {
+    let valid = (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c));
+    if !valid {
+        return Err(anyhow!(
+            "invalid mode '{}': expected 3 or 4 octal digits, e.g. 755 or 0644",
+            mode
+        ));
+    }
+    u32::from_str_radix(mode, 8).map_err(|e| anyhow!("invalid mode '{}': {}", mode, e))
+}
+
+/// `path` joined to `base_dir`, unless it is absolute or `~`-prefixed.
+pub fn resolve_against(base_dir: &Path, path: &str) -> String {
+    if path.starts_with('/') || path.starts_with('~') {
+        return path.to_string();
+    }
+    base_dir.join(path).to_string_lossy().into_owned()
+}
+
+/// Replace a leading `~` with `$HOME`, for paths that never reach a shell
+/// unquoted.
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Why `editor` would not start, if its program (the first word, e.g.
+/// `code` in `code --wait`) is neither on PATH nor an existing path.
+pub fn missing_editor(editor: &str) -> Option<String> {
+    let program = editor.split_whitespace().next()?;
+    let found = if program.contains('/') {
+        Path::new(&expand_home(program)).is_file()
+    } else {
+        on_path(program)
+    };
+    (!found).then(|| {
+        format!(
+            "Editor '{}' was not found on PATH; git would fail to open it for commit messages.",
+            program
+        )
+    })
+}
+
+/// Whether an executable named `program` exists in one of the PATH directories.
+pub fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_params_may_be_quoted_or_bare() {
+        let yaml = "{source: a, dest: b, mode: 600}";
+        let params: TemplateParams = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(params.mode.as_deref(), Some("600"));
+        let yaml = "{source: a, dest: b, mode: '0644'}";
+        let params: TemplateParams = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(params.mode.as_deref(), Some("0644"));
+    }
+
+    #[test]
+    fn parse_mode_accepts_three_or_four_octal_digits() {
+        assert_eq!(parse_mode("755").unwrap(), 0o755);
+        assert_eq!(parse_mode("0644").unwrap(), 0o644);
+        assert_eq!(parse_mode("000").unwrap(), 0);
+        assert_eq!(parse_mode("4755").unwrap(), 0o4755);
+        assert_eq!(parse_mode("7777").unwrap(), 0o7777);
+    }
+
+    #[test]
+    fn parse_mode_rejects_anything_else() {
+        for mode in ["", "abc", "999", "758", "64", "00644", " 644", "+x", "0o644"] {
+            let err = parse_mode(mode).unwrap_err();
+            assert!(err.to_string().contains("expected 3 or 4 octal digits"), "{}: {}", mode, err);
+        }
     }
 }
@@ -0,0 +1,64 @@
+use crate::model::{on_path, Step, StepRuntime, StepStatus};
+use std::process::{Command, Stdio};
+
+/// One-line summary of how the run went, e.g. "3 succeeded, 1 failed".
+/// Failures of `optional` steps are counted separately.
+pub fn summary(steps: &[Step], runtimes: &[StepRuntime]) -> String {
+    let count = |status: StepStatus, optional: Option<bool>| {
+        steps
+            .iter()
+            .zip(runtimes)
+            .filter(|(_, rt)| rt.status == status)
+            .filter(|(step, _)| optional.is_none_or(|optional| step.optional == optional))
+            .count()
+    };
+    let parts = [
+        (count(StepStatus::Success, None), "succeeded"),
+        (count(StepStatus::Failed, Some(false)), "failed"),
+        (count(StepStatus::Failed, Some(true)), "optional failed"),
+        (count(StepStatus::Skipped, None), "skipped"),
+        (count(StepStatus::NotRun, None), "not run"),
+    ];
+    let parts: Vec<String> = parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, label)| format!("{} {}", n, label))
+        .collect();
+    if parts.is_empty() {
+        "No steps were run".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Send a desktop notification with `notify-send` or `osascript`, whichever is
+/// available. Returns a note for the global log when nothing could be sent.
+pub fn send(title: &str, body: &str) -> Option<String> {
+    let mut cmd = if on_path("notify-send") {
+        let mut cmd = Command::new("notify-send");
+        cmd.arg(title).arg(body);
+        cmd
+    } else if on_path("osascript") {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            body.replace('"', "\\\""),
+            title.replace('"', "\\\"")
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e").arg(script);
+        cmd
+    } else {
+        return Some("[notify] No notifier found (notify-send or osascript); skipped.\n".to_string());
+    };
+
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("[notify] Notifier exited with {}.\n", status)),
+        Err(e) => Some(format!("[notify] Failed to run notifier: {}.\n", e)),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::model::{Step, StepRuntime, StepStatus};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::time::Duration;
+
+/// The `--report` file: one entry per step, in file order.
+#[derive(Serialize)]
+struct Report<'a> {
+    /// Why the run stopped before the end, e.g. `global timeout reached`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aborted: Option<&'a str>,
+    steps: Vec<StepReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct StepReport<'a> {
+    name: &'a str,
+    /// `pending`, `running`, `skipped`, `success`, `failed` or `not_run`.
+    status: &'static str,
+    /// Whether the step ended as `success`.
+    succeeded: bool,
+    /// Exit code of the last command the step ran, if it ran any.
+    exit_code: Option<i32>,
+    duration_secs: Option<f64>,
+    /// Every command run, in order (retries included).
+    commands: Vec<CommandReport<'a>>,
+    /// The step log as shown in the UI, for humans.
+    log: &'a str,
+}
+
+#[derive(Serialize)]
+struct CommandReport<'a> {
+    cmd: &'a str,
+    exit_code: i32,
+}
+
+/// Write the JSON report of a finished (or quit) run to `path`, replacing
+/// whatever was there. `aborted` says why a run was cut short.
+pub fn write(
+    path: &str,
+    steps: &[Step],
+    runtimes: &[StepRuntime],
+    durations: &[Option<Duration>],
+    aborted: Option<&str>,
+) -> Result<()> {
+    let steps = steps
+        .iter()
+        .zip(runtimes)
+        .zip(durations)
+        .map(|((step, rt), duration)| StepReport {
+            name: &step.name,
+            status: status_name(rt.status),
+            succeeded: rt.status == StepStatus::Success,
+            exit_code: rt.commands.last().map(|record| record.exit_code),
+            duration_secs: duration.map(|d| d.as_secs_f64()),
+            commands: rt
+                .commands
+                .iter()
+                .map(|record| CommandReport {
+                    cmd: &record.command,
+                    exit_code: record.exit_code,
+                })
+                .collect(),
+            log: &rt.log,
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&Report { aborted, steps })?;
+    fs::write(path, json + "\n").with_context(|| format!("Failed to write report to {}", path))
+}
+
+fn status_name(status: StepStatus) -> &'static str {
+    match status {
+        StepStatus::Pending => "pending",
+        StepStatus::Running => "running",
+        StepStatus::Skipped => "skipped",
+        StepStatus::Success => "success",
+        StepStatus::Failed => "failed",
+        StepStatus::NotRun => "not_run",
+    }
+}
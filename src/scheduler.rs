@@ -0,0 +1,88 @@
+use crate::executor::{run_step, RunContext};
+use crate::model::{Step, StepRuntime, Vars};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+/// Outcome of a step that ran on a worker thread.
+pub struct Finished {
+    pub index: usize,
+    pub runtime: StepRuntime,
+    pub result: Result<()>,
+    /// Vars the step set or changed (e.g. via `capture`).
+    pub vars: Vars,
+    pub secrets: HashSet<String>,
+}
+
+/// Worker pool for the "run all" batch mode: runs up to `jobs` steps at once,
+/// each on its own thread with a snapshot of the run context.
+pub struct Scheduler {
+    pub jobs: usize,
+    /// Steps handed to a worker whose result has not been collected yet.
+    pub running: usize,
+    tx: Sender<Finished>,
+    rx: Receiver<Finished>,
+}
+
+impl Scheduler {
+    pub fn new(jobs: usize) -> Self {
+        let (tx, rx) = channel();
+        Self {
+            jobs: jobs.max(1),
+            running: 0,
+            tx,
+            rx,
+        }
+    }
+
+    pub fn has_capacity(&self) -> bool {
+        self.running < self.jobs
+    }
+
+    /// Run `step` on a new worker thread.
+    pub fn spawn(&mut self, index: usize, step: Step, runtime: StepRuntime, ctx: RunContext) {
+        self.spawn_job(index, runtime, ctx, move |runtime, ctx| run_step(&step, runtime, ctx));
+    }
+
+    /// Run `job` for step `index` on a new worker thread, e.g. the installs
+    /// of an app_selection step once the apps are chosen.
+    pub fn spawn_job<F>(
+        &mut self,
+        index: usize,
+        mut runtime: StepRuntime,
+        mut ctx: RunContext,
+        job: F,
+    ) where
+        F: FnOnce(&mut StepRuntime, &mut RunContext) -> Result<()> + Send + 'static,
+    {
+        let tx = self.tx.clone();
+        self.running += 1;
+        // Not the live log of an earlier run, until the worker's first command.
+        runtime.publish_log(None);
+        thread::spawn(move || {
+            let before = ctx.vars.clone();
+            let result = job(&mut runtime, &mut ctx);
+            let vars = ctx
+                .vars
+                .into_iter()
+                .filter(|(name, value)| before.get(name) != Some(value))
+                .collect();
+            // The receiver only goes away when the TUI exits.
+            let _ = tx.send(Finished {
+                index,
+                runtime,
+                result,
+                vars,
+                secrets: ctx.secrets,
+            });
+        });
+    }
+
+    /// A finished step, if any worker is done. Never blocks.
+    pub fn try_finished(&mut self) -> Option<Finished> {
+        let done = self.rx.try_recv().ok()?;
+        self.running -= 1;
+        Some(done)
+    }
+}
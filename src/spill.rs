@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Keeps step logs in memory bounded (`--max-log-kb`): whenever a log grows
+/// past the limit, everything new is appended to a per-step file and only the
+/// tail stays in memory.
+pub struct LogSpill {
+    limit: usize,
+    dir: PathBuf,
+    /// Per step: the spill file, once there is one, and how many bytes of the
+    /// in-memory log are already in it.
+    files: Vec<Option<(PathBuf, usize)>>,
+}
+
+impl LogSpill {
+    pub fn new(limit_kb: usize, steps: usize) -> Self {
+        Self {
+            limit: limit_kb * 1024,
+            dir: std::env::temp_dir().join(format!("wiza-rs-{}", std::process::id())),
+            files: vec![None; steps],
+        }
+    }
+
+    /// The complete log of step `idx` on disk, if it has spilled.
+    pub fn path(&self, idx: usize) -> Option<&PathBuf> {
+        self.files[idx].as_ref().map(|(path, _)| path)
+    }
+
+    /// Follow the steps to their new indices after a reload;
+    /// `mapping[new] = Some(old)`.
+    pub fn remap(&mut self, mapping: &[Option<usize>]) {
+        self.files = mapping
+            .iter()
+            .map(|old| old.and_then(|i| self.files[i].take()))
+            .collect();
+    }
+
+    /// Note that `log` was shortened after `spill`, so its remaining text is
+    /// already on disk.
+    pub fn mark_saved(&mut self, idx: usize, log: &str) {
+        if let Some((_, written)) = &mut self.files[idx] {
+            *written = log.len();
+        }
+    }
+
+    /// Write the unsaved part of `log` to the step's file and cut the log down
+    /// to its last `limit` bytes, once it is over the limit.
+    pub fn spill(&mut self, idx: usize, name: &str, log: &mut String) -> Result<()> {
+        if self.files[idx].is_none() && log.len() <= self.limit {
+            return Ok(());
+        }
+        let (path, written) = match &mut self.files[idx] {
+            Some(entry) => entry,
+            entry @ None => {
+                fs::create_dir_all(&self.dir)
+                    .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+                entry.insert((self.dir.join(format!("{:03}-{}.log", idx, file_name(name))), 0))
+            }
+        };
+        // The log was reset (e.g. replaced by a fresh runtime): save it all.
+        if *written > log.len() || !log.is_char_boundary(*written) {
+            *written = 0;
+        }
+        if *written < log.len() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&*path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            file.write_all(&log.as_bytes()[*written..])
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        if log.len() > self.limit {
+            let marker = format!("…[earlier output in {}]\n", path.display());
+            let mut cut = log.len() - self.limit.saturating_sub(marker.len());
+            while !log.is_char_boundary(cut) {
+                cut += 1;
+            }
+            // Keep whole lines where there is a newline to cut at.
+            if let Some(nl) = log[cut..].find('\n') {
+                cut += nl + 1;
+            }
+            let tail = log.split_off(cut);
+            *log = marker + &tail;
+        }
+        *written = log.len();
+        Ok(())
+    }
+}
+
+/// `name` with anything but ASCII letters, digits, `-` and `_` replaced.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,179 @@
+use crate::model::{Step, StepRuntime, StepStatus};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// What is saved of a run: which steps succeeded, and which steps file (by
+/// its step names, in order) they belong to.
+#[derive(Debug, Serialize, Deserialize)]
+struct Saved {
+    fingerprint: String,
+    succeeded: Vec<String>,
+}
+
+/// Progress of the runs of one steps file, kept under
+/// `$XDG_STATE_HOME/wiza-rs/` and rewritten whenever a step changes status,
+/// so `--continue` can pick up after an interruption.
+pub struct RunState {
+    pub path: PathBuf,
+}
+
+impl RunState {
+    /// The state file for `yaml_path`, or `None` when there is no home
+    /// directory to keep it in.
+    pub fn for_file(yaml_path: &str) -> Option<Self> {
+        Some(Self {
+            path: state_path(yaml_path, "json")?,
+        })
+    }
+
+    /// `--continue`: mark the steps that succeeded in the saved run as
+    /// `Success`. Returns a note for the log saying what happened.
+    pub fn resume(&self, steps: &[Step], runtimes: &mut [StepRuntime]) -> String {
+        let saved = match self.load() {
+            Ok(Some(saved)) => saved,
+            Ok(None) => {
+                return "[continue] No saved progress; starting from the beginning.\n".to_string()
+            }
+            Err(e) => {
+                return format!("[continue] {:#}; starting from the beginning.\n", e);
+            }
+        };
+        if saved.fingerprint != fingerprint(steps) {
+            return "[continue] The steps file changed since the saved run (steps were renamed, \
+                    added or reordered); starting from the beginning.\n"
+                .to_string();
+        }
+        let mut resumed = 0;
+        for (step, rt) in steps.iter().zip(runtimes.iter_mut()) {
+            if saved.succeeded.contains(&step.name) {
+                rt.status = StepStatus::Success;
+                rt.log.push_str("Succeeded in an earlier run; not run again (--continue).\n");
+                resumed += 1;
+            }
+        }
+        format!(
+            "[continue] {} step(s) succeeded in the earlier run and are marked done.\n",
+            resumed
+        )
+    }
+
+    /// Record which steps have succeeded so far.
+    pub fn save(&self, steps: &[Step], runtimes: &[StepRuntime]) -> Result<()> {
+        let saved = Saved {
+            fingerprint: fingerprint(steps),
+            succeeded: steps
+                .iter()
+                .zip(runtimes)
+                .filter(|(_, rt)| rt.status == StepStatus::Success)
+                .map(|(step, _)| step.name.clone())
+                .collect(),
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&saved)?)
+            .with_context(|| format!("Failed to write state file {}", self.path.display()))
+    }
+
+    fn load(&self) -> Result<Option<Saved>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read state file {}", self.path.display()))?;
+        let saved = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse state file {}", self.path.display()))?;
+        Ok(Some(saved))
+    }
+}
+
+/// Names of the `run_once` steps of one steps file that have succeeded,
+/// kept next to its `RunState` until `--reset-once`. Read and rewritten on
+/// every use, so background workers see each other's markers.
+#[derive(Debug, Clone)]
+pub struct OnceMarkers {
+    pub path: PathBuf,
+}
+
+/// Serialises read-modify-write of marker files between worker threads.
+static MARKERS_LOCK: Mutex<()> = Mutex::new(());
+
+impl OnceMarkers {
+    pub fn for_file(yaml_path: &str) -> Option<Self> {
+        Some(Self {
+            path: state_path(yaml_path, "once.json")?,
+        })
+    }
+
+    /// Whether the step `name` has already run once.
+    pub fn contains(&self, name: &str) -> Result<bool> {
+        let _lock = MARKERS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self.load()?.iter().any(|done| done == name))
+    }
+
+    /// Record that the step `name` has run.
+    pub fn mark(&self, name: &str) -> Result<()> {
+        let _lock = MARKERS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut names = self.load()?;
+        if names.iter().any(|done| done == name) {
+            return Ok(());
+        }
+        names.push(name.to_string());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&names)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// `--reset-once`: forget every marker. Returns how many there were.
+    pub fn clear(&self) -> Result<usize> {
+        let _lock = MARKERS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let count = self.load()?.len();
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .with_context(|| format!("Failed to remove {}", self.path.display()))?;
+        }
+        Ok(count)
+    }
+
+    fn load(&self) -> Result<Vec<String>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", self.path.display()))
+    }
+}
+
+/// `$XDG_STATE_HOME/wiza-rs/<hash of the canonical yaml_path>.<extension>`.
+fn state_path(yaml_path: &str, extension: &str) -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    let path = fs::canonicalize(yaml_path).unwrap_or_else(|_| PathBuf::from(yaml_path));
+    let name = format!("{:016x}.{}", fnv1a(path.to_string_lossy().as_bytes()), extension);
+    Some(dir.join("wiza-rs").join(name))
+}
+
+/// Hash of the step names in order: changes when steps are renamed, added,
+/// removed or reordered, but not when their commands are edited.
+fn fingerprint(steps: &[Step]) -> String {
+    let names: Vec<&str> = steps.iter().map(|step| step.name.as_str()).collect();
+    format!("{:016x}", fnv1a(names.join("\n").as_bytes()))
+}
+
+/// 64-bit FNV-1a, which (unlike `DefaultHasher`) is the same in every build.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
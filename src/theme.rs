@@ -0,0 +1,184 @@
+use crate::model::{StatusStyleConfig, StepKind, StepStatus, ThemeConfig, ThemePreset};
+use anyhow::{anyhow, Result};
+use ratatui::style::Color;
+use std::str::FromStr;
+use unicode_width::UnicodeWidthStr;
+
+/// How one status is drawn: the glyph in the steps list and the colour used
+/// for it in the status bar.
+#[derive(Debug, Clone)]
+pub struct StatusStyle {
+    pub symbol: String,
+    pub color: Color,
+}
+
+/// Resolved look of the TUI, built from the optional `theme` section.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub pending: StatusStyle,
+    pub running: StatusStyle,
+    pub skipped: StatusStyle,
+    pub success: StatusStyle,
+    pub failed: StatusStyle,
+    pub not_run: StatusStyle,
+    /// Marker in front of the selected row.
+    pub cursor: String,
+    /// Flash messages and warnings.
+    pub accent: Color,
+    /// Hints and placeholders.
+    pub muted: Color,
+    /// Step kind tags, as in `[sh]`.
+    pub tags: TagColors,
+    /// Whether the preset draws colours at all (not `mono`).
+    pub colored: bool,
+}
+
+/// Colour of each step kind's tag in the steps list.
+#[derive(Debug, Clone)]
+pub struct TagColors {
+    pub script: Color,
+    pub edit: Color,
+    pub git: Color,
+    pub service: Color,
+    pub apps: Color,
+    pub prompt: Color,
+}
+
+const DARK_TAGS: TagColors = TagColors {
+    script: Color::Gray,
+    edit: Color::Cyan,
+    git: Color::LightRed,
+    service: Color::LightBlue,
+    apps: Color::LightMagenta,
+    prompt: Color::LightYellow,
+};
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::preset(ThemePreset::Default)
+    }
+}
+
+impl Theme {
+    pub fn preset(preset: ThemePreset) -> Self {
+        let style = |symbol: &str, color| StatusStyle {
+            symbol: symbol.to_string(),
+            color,
+        };
+        let dark = Self {
+            pending: style("[ ]", Color::White),
+            running: style("[>]", Color::Yellow),
+            skipped: style("[-]", Color::Blue),
+            success: style("[✓]", Color::Green),
+            failed: style("[✗]", Color::Red),
+            not_run: style("[·]", Color::DarkGray),
+            cursor: "➤".to_string(),
+            accent: Color::Yellow,
+            muted: Color::DarkGray,
+            tags: DARK_TAGS,
+            colored: true,
+        };
+        match preset {
+            ThemePreset::Default => dark,
+            ThemePreset::Ascii => Self {
+                success: style("[+]", Color::Green),
+                failed: style("[x]", Color::Red),
+                not_run: style("[.]", Color::DarkGray),
+                cursor: ">".to_string(),
+                ..dark
+            },
+            // No white, yellow or light variants: they fade on a light background.
+            ThemePreset::Light => Self {
+                pending: style("[ ]", Color::Black),
+                running: style("[>]", Color::Magenta),
+                not_run: style("[·]", Color::Gray),
+                accent: Color::Magenta,
+                muted: Color::Gray,
+                tags: TagColors {
+                    script: Color::DarkGray,
+                    edit: Color::Cyan,
+                    git: Color::Red,
+                    service: Color::Blue,
+                    apps: Color::Magenta,
+                    prompt: Color::Rgb(0x80, 0x60, 0x00),
+                },
+                ..dark
+            },
+            ThemePreset::Mono => Self {
+                colored: false,
+                ..dark
+            },
+        }
+    }
+
+    /// Start from the preset (`--theme`, else the file's `preset`) and apply
+    /// per-status overrides. Without colour the default base is the ASCII
+    /// preset.
+    pub fn resolve(config: &ThemeConfig, chosen: Option<ThemePreset>, color: bool) -> Result<Self> {
+        let fallback = if color { ThemePreset::Default } else { ThemePreset::Ascii };
+        let mut theme = Self::preset(chosen.or(config.preset).unwrap_or(fallback));
+        let overrides = [
+            (&config.pending, &mut theme.pending, "pending"),
+            (&config.running, &mut theme.running, "running"),
+            (&config.skipped, &mut theme.skipped, "skipped"),
+            (&config.success, &mut theme.success, "success"),
+            (&config.failed, &mut theme.failed, "failed"),
+            (&config.not_run, &mut theme.not_run, "not_run"),
+        ];
+        for (config, style, name) in overrides {
+            if let Some(config) = config {
+                apply(config, style, name)?;
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Columns taken by the widest status symbol, so narrower ones can be
+    /// padded and step names line up.
+    pub fn symbol_width(&self) -> usize {
+        [&self.pending, &self.running, &self.skipped, &self.success, &self.failed, &self.not_run]
+            .iter()
+            .map(|style| style.symbol.width())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn tag(&self, kind: &StepKind) -> Color {
+        match kind {
+            StepKind::Script => self.tags.script,
+            StepKind::AddText { .. }
+            | StepKind::ReplaceInFile { .. }
+            | StepKind::Symlink { .. }
+            | StepKind::Template { .. }
+            | StepKind::LineInFile { .. } => self.tags.edit,
+            StepKind::GitConfig { .. }
+            | StepKind::GitClone { .. }
+            | StepKind::Download { .. } => self.tags.git,
+            StepKind::Service { .. } => self.tags.service,
+            StepKind::AppSelection { .. } | StepKind::Package { .. } => self.tags.apps,
+            StepKind::Prompt { .. } => self.tags.prompt,
+        }
+    }
+
+    pub fn status(&self, status: StepStatus) -> &StatusStyle {
+        match status {
+            StepStatus::Pending => &self.pending,
+            StepStatus::Running => &self.running,
+            StepStatus::Skipped => &self.skipped,
+            StepStatus::Success => &self.success,
+            StepStatus::Failed => &self.failed,
+            StepStatus::NotRun => &self.not_run,
+        }
+    }
+}
+
+fn apply(config: &StatusStyleConfig, style: &mut StatusStyle, name: &str) -> Result<()> {
+    if let Some(symbol) = &config.symbol {
+        style.symbol = symbol.clone();
+    }
+    if let Some(color) = &config.color {
+        style.color = Color::from_str(color)
+            .map_err(|_| anyhow!("theme.{}.color: unknown color '{}'", name, color))?;
+    }
+    Ok(())
+}
@@ -1,40 +1,158 @@
-use crate::executor::{apply_app_selection, apply_git_config, run_step, start_sudo_session};
-use crate::model::{Step, StepKind, StepRuntime, StepStatus};
-use anyhow::Result;
+use crate::cli::Cli;
+use crate::clipboard;
+use crate::events::EventSink;
+use crate::executor::{
+    apply_app_selection, apply_git_config, apply_prompt, clear_interrupt, deadline_reached,
+    drop_old_lines, explain_step, git_config_commands, git_editor, install_interrupt_handler,
+    interpolate, preview_add_text, run_command, run_step, start_sudo_session, AddTextPreview,
+    GlobalTimeout, RunContext,
+};
+use crate::model::{
+    dependencies, missing_editor, next_ready, InstallOrder, Step, StepFile, StepKind, StepRuntime,
+    StepStatus, Vars,
+};
+use crate::notify;
+use crate::report;
+use crate::scheduler::Scheduler;
+use crate::spill::LogSpill;
+use crate::state::RunState;
+use crate::theme::Theme;
+use anyhow::{Context, Result};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    cursor::MoveTo,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     execute,
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph},
     Terminal,
 };
 use std::io::{stdout, Stdout};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-pub struct App<'a> {
-    pub steps: &'a [Step],
+pub struct App {
+    /// Shared so code holding a step can still call `&mut self` methods;
+    /// replaced wholesale when the file is reloaded.
+    pub steps: Rc<[Step]>,
     pub runtimes: Vec<StepRuntime>,
     pub current: usize,
     pub global_log: String,
     // NEW: vertical scroll offset for the current step's log
     pub log_scroll: u16,
+    /// The log panel shows `global_log` instead of the step log (`G`).
+    pub show_global_log: bool,
+    /// The log takes the whole screen, hiding the steps list (`z`).
+    pub full_log: bool,
+    pub global_log_scroll: u16,
     pub mode: InteractiveMode,
+    pub ctx: RunContext,
+    /// Animation frame for the running-step spinner, advanced on every draw.
+    pub spinner_frame: usize,
+    pub events: Option<EventSink>,
+    /// Last status reported to `events` for each step.
+    reported: Vec<StepStatus>,
+    /// Where progress is saved for `--continue`; dropped if it can't be written.
+    pub state: Option<RunState>,
+    /// Selection/offset of the steps list, kept in sync with `current` so
+    /// the view follows the cursor when there are more steps than rows.
+    pub list_state: ListState,
+    /// Leave succeeded and skipped steps out of the list (`h`).
+    pub hide_done: bool,
+    pub theme: Theme,
+    /// One-off message shown in the status bar until the next key press.
+    pub flash: Option<String>,
+    /// Send a desktop notification when the run finishes (`--notify`).
+    pub notify: bool,
+    notified: bool,
+    /// Indices of each step's `depends_on` steps.
+    deps: Vec<Vec<usize>>,
+    /// Workers for the "run all" batch mode.
+    pub scheduler: Scheduler,
+    /// Whether the batch mode is active, i.e. ready steps get scheduled.
+    pub batch: bool,
+    /// Start the batch once the selected step succeeds (`R`).
+    batch_after_current: bool,
+    /// `--fail-fast`: a failure that stops the batch marks the steps it did
+    /// not reach `NotRun`.
+    fail_fast: bool,
+    /// `--select`: the intro leads to the step checklist.
+    select_steps: bool,
+    /// `--report`: where to write the JSON report on quit.
+    report: Option<String>,
+    /// Key press of the command picked in the palette, handled next.
+    replay: Option<KeyCode>,
+    /// When each step last entered `Running`, and how long its last run took.
+    started: Vec<Option<Instant>>,
+    pub durations: Vec<Option<Duration>>,
+    /// The summary screen has been shown automatically once already.
+    summary_shown: bool,
+    /// Draw on the alternate screen (off with `--no-alt-screen`).
+    pub alt_screen: bool,
+    /// Draw with colours (off with `--no-color` / `NO_COLOR`).
+    pub color: bool,
+    /// Moves the bulk of long step logs to disk (`--max-log-kb`).
+    pub spill: Option<LogSpill>,
+    /// Oldest lines beyond this many are dropped from step logs.
+    pub max_log_lines: Option<usize>,
+    /// Steps file to re-read on `F5`.
+    pub yaml_path: String,
+    /// Time limit of the startup `sudo -v` (`--sudo-timeout`).
+    pub sudo_timeout: Option<u64>,
+    /// Width of the steps pane in percent; the log gets the rest (`<`/`>`).
+    pub split: u16,
 }
 
-impl<'a> App<'a> {
-    pub fn new(steps: &'a [Step]) -> Self {
+impl App {
+    pub fn new(steps: Rc<[Step]>, vars: Vars, events: Option<EventSink>) -> Self {
         Self {
-            steps,
             runtimes: vec![StepRuntime::default(); steps.len()],
             current: 0,
             global_log: String::new(),
             log_scroll: 0,
+            show_global_log: false,
+            full_log: false,
+            global_log_scroll: 0,
             mode: InteractiveMode::None,
+            ctx: RunContext::new(vars),
+            spinner_frame: 0,
+            events,
+            reported: vec![StepStatus::default(); steps.len()],
+            state: None,
+            list_state: ListState::default(),
+            hide_done: false,
+            theme: Theme::default(),
+            flash: None,
+            notify: false,
+            notified: false,
+            deps: dependencies(&steps),
+            scheduler: Scheduler::new(1),
+            batch: false,
+            batch_after_current: false,
+            fail_fast: false,
+            select_steps: false,
+            report: None,
+            replay: None,
+            started: vec![None; steps.len()],
+            durations: vec![None; steps.len()],
+            summary_shown: false,
+            alt_screen: true,
+            color: true,
+            spill: None,
+            max_log_lines: None,
+            yaml_path: String::new(),
+            sudo_timeout: None,
+            split: DEFAULT_SPLIT,
+            steps,
         }
     }
 
@@ -49,19 +167,530 @@ impl<'a> App<'a> {
     fn reset_scroll(&mut self) {
         self.log_scroll = 0;
     }
+
+    /// Scroll offset of whichever log the panel is showing.
+    fn scroll_mut(&mut self) -> &mut u16 {
+        if self.show_global_log {
+            &mut self.global_log_scroll
+        } else {
+            &mut self.log_scroll
+        }
+    }
+
+    /// Steps shown in the list; see [`visible_rows`].
+    fn visible_indices(&self) -> Vec<usize> {
+        visible_rows(&self.runtimes, self.hide_done)
+    }
+
+    /// Keep the cursor on a visible step after the filter or a status changed.
+    fn clamp_current(&mut self) {
+        let current = clamp_to_visible(&self.visible_indices(), self.current);
+        if current != self.current {
+            self.current = current;
+            self.reset_scroll();
+        }
+    }
+
+    /// Select the next (or previous) visible step, if there is one.
+    fn move_cursor(&mut self, forward: bool) {
+        let visible = self.visible_indices();
+        let target = if forward {
+            visible.into_iter().find(|&idx| idx > self.current)
+        } else {
+            visible.into_iter().rev().find(|&idx| idx < self.current)
+        };
+        if let Some(idx) = target {
+            self.current = idx;
+            self.reset_scroll();
+        }
+    }
+
+    /// Emit an event for every step whose status changed since the last call,
+    /// and save the progress if any did. A failing sink or state file is
+    /// dropped with a note in the global log; the run goes on.
+    fn sync_events(&mut self) {
+        let mut changed = false;
+        for idx in 0..self.runtimes.len() {
+            let status = self.runtimes[idx].status;
+            if status == self.reported[idx] {
+                continue;
+            }
+            changed = true;
+            if status == StepStatus::Running {
+                self.started[idx] = Some(Instant::now());
+            } else if self.reported[idx] == StepStatus::Running {
+                self.durations[idx] = self.started[idx].map(|start| start.elapsed());
+                self.runtimes[idx].set_current_command(None);
+            }
+            self.reported[idx] = status;
+            if let Some(sink) = &mut self.events {
+                if let Err(e) = sink.emit(idx, &self.steps[idx].name, status) {
+                    self.global_log
+                        .push_str(&format!("[events] {:#}; no further events will be written.\n", e));
+                    self.events = None;
+                }
+            }
+        }
+        if let (true, Some(state)) = (changed, &self.state) {
+            if let Err(e) = state.save(&self.steps, &self.runtimes) {
+                self.global_log
+                    .push_str(&format!("[continue] {:#}; progress will not be saved.\n", e));
+                self.state = None;
+            }
+        }
+    }
+
+    /// On quit (and when `--fail-fast` stops the batch), steps never reached
+    /// become `NotRun` so they are not mistaken for steps that were
+    /// deliberately skipped.
+    fn mark_not_run(&mut self) {
+        for rt in &mut self.runtimes {
+            if rt.status == StepStatus::Pending {
+                rt.status = StepStatus::NotRun;
+            }
+        }
+        self.sync_events();
+    }
+
+    /// Move the cursor to the first step with `status`, or flash a note.
+    fn jump_to_first(&mut self, status: StepStatus) {
+        match self.runtimes.iter().position(|rt| rt.status == status) {
+            Some(idx) => {
+                self.current = idx;
+                self.reset_scroll();
+            }
+            None => self.flash = Some(format!("No {} step.", status.label().to_lowercase())),
+        }
+    }
+
+    /// Send the `--notify` summary, at most once per run.
+    fn notify_finished(&mut self) {
+        if !self.notify || self.notified {
+            return;
+        }
+        self.notified = true;
+        let body = notify::summary(&self.steps, &self.runtimes);
+        if let Some(note) = notify::send("wiza-rs: run finished", &body) {
+            self.global_log.push_str(&note);
+        }
+    }
+
+    /// Names of the steps `idx` still waits on; success or skip satisfies a dependency.
+    fn unmet_dependencies(&self, idx: usize) -> Vec<&str> {
+        self.deps[idx]
+            .iter()
+            .filter(|&&dep| {
+                !matches!(self.runtimes[dep].status, StepStatus::Success | StepStatus::Skipped)
+            })
+            .map(|&dep| self.steps[dep].name.as_str())
+            .collect()
+    }
+
+    /// Collect finished workers (batch, background steps, app installs), then,
+    /// in batch mode, hand ready steps to the scheduler. The batch stops at the
+    /// first failure and pauses at steps that need the terminal, selecting them.
+    fn pump_batch(&mut self) {
+        let steps = Rc::clone(&self.steps);
+        while let Some(done) = self.scheduler.try_finished() {
+            let mut runtime = done.runtime;
+            self.ctx.vars.extend(done.vars);
+            self.ctx.secrets.extend(done.secrets);
+            if let Err(e) = done.result {
+                runtime.status = StepStatus::Failed;
+                runtime.log.push_str(&self.ctx.redact(&format!("\n[ERROR] {:#}\n", e)));
+            }
+            let step = &steps[done.index];
+            if runtime.status == StepStatus::Failed && step.optional {
+                runtime.log.push_str("\nOptional step failed; the batch carries on.\n");
+            } else if runtime.status == StepStatus::Failed && self.batch {
+                self.batch = false;
+                self.current = done.index;
+                self.reset_scroll();
+                self.flash = Some(format!("Batch stopped: '{}' failed.", step.name));
+                if self.fail_fast {
+                    self.mark_not_run();
+                    self.flash = Some(format!(
+                        "Batch stopped: '{}' failed; the remaining steps were not run.",
+                        step.name
+                    ));
+                }
+            } else if !self.batch {
+                self.flash =
+                    Some(format!("'{}' finished: {}.", step.name, runtime.status.label()));
+            }
+            self.runtimes[done.index] = runtime;
+            // The app checklist stays up, with its gauge, until the installs end.
+            if matches!(&self.mode, InteractiveMode::AppSelection(state) if state.installing)
+                && done.index == self.current
+            {
+                self.mode = InteractiveMode::None;
+                self.reset_scroll();
+            }
+        }
+
+        if !self.batch {
+            return;
+        }
+        while self.scheduler.has_capacity() {
+            let Some(idx) = next_ready(&self.deps, &self.runtimes) else { break };
+            let step = &steps[idx];
+            if step.needs_terminal() {
+                if self.scheduler.running == 0 {
+                    self.batch = false;
+                    self.current = idx;
+                    self.reset_scroll();
+                    self.flash =
+                        Some(format!("Batch paused at '{}': it needs your input.", step.name));
+                }
+                return;
+            }
+            self.runtimes[idx].status = StepStatus::Running;
+            self.scheduler
+                .spawn(idx, step.clone(), self.runtimes[idx].clone(), self.ctx.clone());
+        }
+        if self.scheduler.running == 0 {
+            self.batch = false;
+            self.flash = Some("Batch finished.".to_string());
+        }
+    }
+
+    /// Bound finished or idle step logs: under `--max-log-kb` move their
+    /// excess to spill files, then under `--max-log-lines` drop their oldest
+    /// lines. A spill write failure turns spilling off with a note.
+    fn limit_logs(&mut self) {
+        for (idx, rt) in self.runtimes.iter_mut().enumerate() {
+            if rt.status == StepStatus::Running {
+                continue;
+            }
+            if let Some(spill) = &mut self.spill {
+                if let Err(e) = spill.spill(idx, &self.steps[idx].name, &mut rt.log) {
+                    self.global_log.push_str(&format!(
+                        "[spill] {:#}; logs are kept in memory from now on.\n",
+                        e
+                    ));
+                    self.spill = None;
+                }
+            }
+            if let Some(max) = self.max_log_lines {
+                if drop_old_lines(&mut rt.log, max) {
+                    if let Some(spill) = &mut self.spill {
+                        spill.mark_saved(idx, &rt.log);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Re-read and validate the steps file (`F5`), then swap in its steps.
+    /// A step whose name is still there keeps its status, log and duration;
+    /// vars the file adds are merged in. On error nothing changes.
+    fn reload(&mut self) -> Result<()> {
+        let text = std::fs::read_to_string(&self.yaml_path)
+            .with_context(|| format!("Failed to read {}", self.yaml_path))?;
+        let file = StepFile::parse(&text, &self.yaml_path)?;
+        file.validate().context("YAML failed validation")?;
+
+        // New index -> old index, matching duplicate names in file order.
+        let mut taken = vec![false; self.steps.len()];
+        let mapping: Vec<Option<usize>> = file
+            .steps
+            .iter()
+            .map(|step| {
+                let old = (0..self.steps.len())
+                    .find(|&i| !taken[i] && self.steps[i].name == step.name)?;
+                taken[old] = true;
+                Some(old)
+            })
+            .collect();
+        let current_name = self.steps[self.current].name.clone();
+
+        self.runtimes = mapping
+            .iter()
+            .map(|old| old.map(|i| self.runtimes[i].clone()).unwrap_or_default())
+            .collect();
+        self.reported = mapping
+            .iter()
+            .map(|old| old.map(|i| self.reported[i]).unwrap_or_default())
+            .collect();
+        self.started = mapping.iter().map(|old| old.and_then(|i| self.started[i])).collect();
+        self.durations = mapping.iter().map(|old| old.and_then(|i| self.durations[i])).collect();
+        if let Some(spill) = &mut self.spill {
+            spill.remap(&mapping);
+        }
+        for (name, value) in file.vars {
+            self.ctx.vars.entry(name).or_insert(value);
+        }
+        self.deps = dependencies(&file.steps);
+        self.current = file
+            .steps
+            .iter()
+            .position(|step| step.name == current_name)
+            .unwrap_or(self.current.min(file.steps.len() - 1));
+        self.steps = file.steps.into();
+        self.summary_shown = false;
+        self.reset_scroll();
+        self.flash = Some(format!("Reloaded {} ({} steps).", self.yaml_path, self.steps.len()));
+        Ok(())
+    }
+
+    /// Copy the last failing command of the selected step to the clipboard
+    /// (`Y`), noting it in the step log.
+    fn copy_failed_command(&mut self) {
+        let Some(cmd) = self.current_runtime().last_failed_command().map(|c| c.command.clone())
+        else {
+            self.flash = Some("No failed command in this step.".to_string());
+            return;
+        };
+        match clipboard::copy(&cmd) {
+            Ok(via) => {
+                self.current_runtime_mut()
+                    .log
+                    .push_str(&format!("\nCopied failing command via {}: {}\n", via, cmd));
+                self.flash = Some(format!("Copied failing command via {}.", via));
+            }
+            Err(e) => self.flash = Some(e),
+        }
+    }
+
+    /// Run the `size_command` of the app under the cursor, once per app.
+    fn load_app_size(&mut self) {
+        let InteractiveMode::AppSelection(state) = &mut self.mode else {
+            return;
+        };
+        let StepKind::AppSelection { params } = &self.steps[self.current].kind else {
+            return;
+        };
+        let idx = state.cursor;
+        let Some(cmd) = &params.apps[idx].size_command else {
+            return;
+        };
+        if state.sizes[idx].is_some() {
+            return;
+        }
+        let size = match run_command(&interpolate(cmd, &self.ctx.vars)) {
+            Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string(),
+            _ => "size unknown".to_string(),
+        };
+        state.sizes[idx] = Some(size);
+    }
+
+    /// After `R`: once the retried step is done, continue with the batch if it
+    /// succeeded.
+    fn continue_after_retry(&mut self) {
+        if !self.batch_after_current || !matches!(self.mode, InteractiveMode::None) {
+            return;
+        }
+        self.batch_after_current = false;
+        self.flash = Some(match self.current_runtime().status {
+            StepStatus::Success | StepStatus::Skipped => {
+                self.batch = true;
+                "Retry done; running all remaining steps...".to_string()
+            }
+            StepStatus::Pending => "Retry cancelled.".to_string(),
+            _ => "Retry failed; the remaining steps were not started.".to_string(),
+        });
+    }
+
+    /// Wrap up the run for quitting (`q` on the summary / Ctrl-C). Refuses,
+    /// with a note, while batch steps are still running.
+    fn try_quit(&mut self) -> bool {
+        if self.scheduler.running > 0 {
+            self.batch = false;
+            self.flash = Some(format!(
+                "{} step(s) still running; quit once they finish.",
+                self.scheduler.running
+            ));
+            return false;
+        }
+        self.mark_not_run();
+        self.notify_finished();
+        true
+    }
+
+    /// `--report`, if asked for; `aborted` says why the run was cut short.
+    fn write_report(&self, aborted: Option<&str>) -> Result<()> {
+        match &self.report {
+            Some(path) => {
+                report::write(path, &self.steps, &self.runtimes, &self.durations, aborted)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn all_done(&self) -> bool {
+        self.runtimes
+            .iter()
+            .all(|rt| !matches!(rt.status, StepStatus::Pending | StepStatus::Running))
+    }
+
+    /// `--select`: steps left unticked are skipped (by hand, so `s` brings
+    /// them back), and the cursor goes to the first step still to run.
+    fn apply_step_selection(&mut self, selected: &[bool]) {
+        for (rt, &on) in self.runtimes.iter_mut().zip(selected) {
+            if !on && rt.status == StepStatus::Pending {
+                rt.status = StepStatus::Skipped;
+                rt.set_by_hand = true;
+                rt.log.push_str("Left out at startup (--select); press s to include it.\n");
+            }
+        }
+        if let Some(idx) = self.runtimes.iter().position(|rt| rt.status == StepStatus::Pending) {
+            self.current = idx;
+            self.reset_scroll();
+        }
+    }
+
+    /// Whether `--fail-fast` left steps `NotRun` that `a` can run again.
+    fn has_not_run(&self) -> bool {
+        self.runtimes.iter().any(|rt| rt.status == StepStatus::NotRun)
+    }
+
+    fn spinner(&self) -> &'static str {
+        SPINNER[self.spinner_frame % SPINNER.len()]
+    }
 }
 
+const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+
+/// Steps pane width in percent, unless `--split` says otherwise.
+pub const DEFAULT_SPLIT: u16 = 35;
+/// Bounds for the steps pane width, so neither pane disappears.
+pub const MIN_SPLIT: u16 = 15;
+pub const MAX_SPLIT: u16 = 70;
+/// How much `<` / `>` move the split.
+const SPLIT_STEP: u16 = 5;
+
 #[derive(Debug, Clone)]
 pub enum InteractiveMode {
     None,
     AppSelection(AppSelectionState),
     GitConfig(GitConfigState),
+    Prompt(PromptState),
+    /// Proposed `add_text` edit, or why it cannot be applied, awaiting confirmation.
+    AddTextPreview(Result<AddTextPreview, String>),
+    FailureDetail,
+    /// Full-screen list of every key binding.
+    Help,
+    /// Final status, duration and last log line of every step; `cursor` is
+    /// the highlighted row.
+    Summary { cursor: usize },
+    /// Summary of the parsed steps shown before anything runs, with the
+    /// non-fatal validation notes.
+    Intro(Vec<String>),
+    /// Why `F5` could not reload the steps file.
+    ReloadError(String),
+    /// `e`: what the selected step would do, scrolled by `scroll` lines.
+    Explain { text: String, scroll: u16 },
+    /// `--select`: checklist of the steps to include in this session.
+    StepSelection(StepSelectionState),
+    /// `:`: the command palette.
+    Palette(PaletteState),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PaletteState {
+    pub query: String,
+    /// Index into the commands matching `query`.
+    pub cursor: usize,
+}
+
+/// Commands of the palette (`:`): name, key, and the key press that runs it
+/// in the main view.
+const PALETTE_COMMANDS: &[(&str, &str, KeyCode)] = &[
+    ("Run the selected step", "Enter", KeyCode::Enter),
+    ("Run all remaining steps / pause the batch", "a", KeyCode::Char('a')),
+    ("Retry the selected step, then run all", "R", KeyCode::Char('R')),
+    ("Skip the selected step / undo the skip", "s", KeyCode::Char('s')),
+    ("Mark the selected step done / undo", "m", KeyCode::Char('m')),
+    ("Next step", "n", KeyCode::Char('n')),
+    ("Previous step", "p", KeyCode::Char('p')),
+    ("Jump to the first failed step", "[", KeyCode::Char('[')),
+    ("Jump to the first pending step", "]", KeyCode::Char(']')),
+    ("Hide / show succeeded and skipped steps", "h", KeyCode::Char('h')),
+    ("Explain the selected step", "e", KeyCode::Char('e')),
+    ("Failure details", "d", KeyCode::Char('d')),
+    ("Copy the failing command", "Y", KeyCode::Char('Y')),
+    ("Toggle the global log", "G", KeyCode::Char('G')),
+    ("Full-screen log", "z", KeyCode::Char('z')),
+    ("Narrow the steps pane", "<", KeyCode::Char('<')),
+    ("Widen the steps pane", ">", KeyCode::Char('>')),
+    ("Open the complete log in $PAGER", "o", KeyCode::Char('o')),
+    ("Reload the steps file", "F5", KeyCode::F(5)),
+    ("Show the run summary", "S", KeyCode::Char('S')),
+    ("Show all keys", "?", KeyCode::Char('?')),
+    ("Quit", "q", KeyCode::Char('q')),
+];
+
+/// How well `query` matches `text` as a case-insensitive subsequence: the
+/// number of characters skipped between matches (lower is better), or
+/// `None` when it doesn't match.
+fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    let mut skipped = 0;
+    let mut started = false;
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let c = chars.next()?;
+            if c == wanted {
+                started = true;
+                break;
+            }
+            if started {
+                skipped += 1;
+            }
+        }
+    }
+    Some(skipped)
+}
+
+impl PaletteState {
+    /// Indices into `PALETTE_COMMANDS` matching the query, best first.
+    fn matches(&self) -> Vec<usize> {
+        let mut scored: Vec<(usize, usize)> = PALETTE_COMMANDS
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (name, _, _))| Some((fuzzy_score(&self.query, name)?, idx)))
+            .collect();
+        scored.sort();
+        scored.into_iter().map(|(_, idx)| idx).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StepSelectionState {
+    pub cursor: usize,
+    pub selected: Vec<bool>,
 }
 
 #[derive(Debug, Clone)]
 pub struct AppSelectionState {
     pub cursor: usize,
     pub selected: Vec<bool>,
+    /// Indices of the selected apps, in the order they were ticked.
+    pub order: Vec<usize>,
+    /// Output of each app's `size_command`, once it has run.
+    pub sizes: Vec<Option<String>>,
+    /// The selection was confirmed and a worker is installing it.
+    pub installing: bool,
+}
+
+impl AppSelectionState {
+    /// Tick or untick app `idx`, keeping `order` in step.
+    fn set(&mut self, idx: usize, on: bool) {
+        if self.selected[idx] == on {
+            return;
+        }
+        self.selected[idx] = on;
+        if on {
+            self.order.push(idx);
+        } else {
+            self.order.retain(|&i| i != idx);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -77,141 +706,548 @@ pub struct GitConfigState {
     pub name: String,
     pub email: String,
     pub editor: String,
+    /// Enter was pressed once: the commands are shown for a second Enter
+    /// (or `y`) before they change the global git config.
+    pub confirm: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptState {
+    /// Index of the field currently being edited.
+    pub field: usize,
+    pub values: Vec<String>,
 }
 
-pub fn run_tui(steps: &[Step]) -> Result<()> {
-    // Initialize TUI.
+pub fn run_tui(steps_file: &StepFile, cli: &Cli) -> Result<()> {
+    let events = cli.events.as_deref().map(EventSink::open).transpose()?;
+    let theme = Theme::resolve(&steps_file.theme, cli.theme, cli.color())?;
+    let mut app = App::new(steps_file.steps.clone().into(), steps_file.vars.clone(), events);
+    app.theme = theme;
+    app.color = cli.color() && app.theme.colored;
+    app.notify = cli.notify;
+    app.scheduler = Scheduler::new(cli.jobs);
+    app.spill = cli.max_log_kb.map(|kb| LogSpill::new(kb, steps_file.steps.len()));
+    app.max_log_lines = cli.max_log_lines;
+    app.yaml_path = cli.yaml_path.clone();
+    app.sudo_timeout = cli.sudo_timeout;
+    app.fail_fast = cli.fail_fast;
+    app.select_steps = cli.select;
+    app.report = cli.report.clone();
+    app.split = cli.split.unwrap_or(DEFAULT_SPLIT);
+    app.state = RunState::for_file(&cli.yaml_path);
+    if let (true, Some(state)) = (cli.resume, &app.state) {
+        let note = state.resume(&app.steps, &mut app.runtimes);
+        app.global_log.push_str(&note);
+        // Steps done in the earlier run are not news for `--events`.
+        for (reported, rt) in app.reported.iter_mut().zip(&app.runtimes) {
+            *reported = rt.status;
+        }
+        if let Some(idx) = app.runtimes.iter().position(|rt| rt.status != StepStatus::Success) {
+            app.current = idx;
+        }
+    }
+    app.ctx = RunContext::prepare(steps_file, cli, &mut app.global_log)?;
+    if cli.select {
+        app.mode = step_selection(&app);
+    }
+    if !cli.yes {
+        let warnings = steps_file.warnings(&cli.base_dir());
+        let warnings = warnings.into_iter().map(|w| w.message).collect();
+        app.mode = InteractiveMode::Intro(warnings);
+    }
+
+    install_interrupt_handler()?;
+
+    app.alt_screen = !cli.no_alt_screen;
+
+    // Initialize TUI. Without the alternate screen the TUI draws over the
+    // visible screen, and its last frame stays in the scrollback.
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    if app.alt_screen {
+        execute!(stdout, EnterAlternateScreen)?;
+    } else {
+        execute!(stdout, Clear(ClearType::All))?;
+    }
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run(&mut terminal, steps);
+    let alt_screen = app.alt_screen;
+    let res = run(&mut terminal, app);
 
     // Restore terminal.
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        crossterm::cursor::Show
-    )?;
+    if alt_screen {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    } else {
+        // Park the cursor below the final frame.
+        let height = terminal.size()?.height;
+        execute!(terminal.backend_mut(), MoveTo(0, height.saturating_sub(1)))?;
+        println!();
+    }
+    execute!(terminal.backend_mut(), crossterm::cursor::Show)?;
     terminal.show_cursor()?;
 
     res
 }
 
-fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Result<()> {
-    let mut app = App::new(steps);
+/// Leave raw mode before running commands. Interactive steps also get the
+/// main screen back (or a cleared one, without the alternate screen) so the
+/// child owns the real terminal.
+fn suspend_tui(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    interactive: bool,
+    alt_screen: bool,
+) -> Result<()> {
+    clear_interrupt();
+    disable_raw_mode()?;
+    if interactive {
+        if alt_screen {
+            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        } else {
+            execute!(terminal.backend_mut(), Clear(ClearType::All), MoveTo(0, 0))?;
+        }
+        execute!(terminal.backend_mut(), crossterm::cursor::Show)?;
+    }
+    Ok(())
+}
+
+/// Undo `suspend_tui` once the commands are done.
+fn resume_tui(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    interactive: bool,
+    alt_screen: bool,
+) -> Result<()> {
+    enable_raw_mode()?;
+    if interactive {
+        if alt_screen {
+            execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+        }
+        terminal.clear()?;
+    }
+    Ok(())
+}
+
+/// Start sudo before the first step can run.
+fn start_session(app: &mut App) -> Result<()> {
+    // Temporarily leave raw mode to let sudo prompt if needed
+    disable_raw_mode()?;
+    let mut dummy_runtime = StepRuntime::default();
+    start_sudo_session(&mut dummy_runtime.log, app.sudo_timeout)?;
+    enable_raw_mode()?;
+    app.global_log.push_str(&dummy_runtime.log);
+    Ok(())
+}
+
+/// What `Enter` does on the selected step: open its form or preview, or run it.
+fn start_current_step(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+) -> Result<()> {
+    let steps = Rc::clone(&app.steps);
+    let step = &steps[app.current];
+    match &step.kind {
+        StepKind::AppSelection { params } => {
+            // Enter interactive app selection mode.
+            let rt = app.current_runtime_mut();
+            rt.status = StepStatus::Running;
+            rt.log.push_str(&format!(
+                "== Running step: {} (app selection) ==\n",
+                step.name
+            ));
+
+            // Initialise selection state.
+            let state = AppSelectionState {
+                cursor: 0,
+                selected: params.apps.iter().map(|app| app.default).collect(),
+                order: (0..params.apps.len()).filter(|&i| params.apps[i].default).collect(),
+                sizes: vec![None; params.apps.len()],
+                installing: false,
+            };
+            app.mode = InteractiveMode::AppSelection(state);
+            app.reset_scroll();
+        }
+        StepKind::GitConfig { params } => {
+            let rt = app.current_runtime_mut();
+            rt.status = StepStatus::Running;
+            rt.log.push_str(&format!(
+                "== Running step: {} (git config) ==\n",
+                step.name
+            ));
+
+            let state = GitConfigState {
+                field: GitField::Name,
+                name: String::new(),
+                email: String::new(),
+                editor: preferred_editor(&params.default_editor),
+                confirm: false,
+            };
+            app.mode = InteractiveMode::GitConfig(state);
+            app.reset_scroll();
+        }
+        StepKind::Prompt { params } => {
+            let rt = app.current_runtime_mut();
+            rt.status = StepStatus::Running;
+            rt.log.push_str(&format!(
+                "== Running step: {} (prompt) ==\n",
+                step.name
+            ));
+
+            // Pre-fill with values from earlier prompts, then defaults.
+            let values = params
+                .fields
+                .iter()
+                .map(|field| {
+                    app.ctx
+                        .vars
+                        .get(&field.var)
+                        .cloned()
+                        .or_else(|| field.default.clone())
+                        .unwrap_or_default()
+                })
+                .collect();
+            let state = PromptState { field: 0, values };
+            app.mode = InteractiveMode::Prompt(state);
+            app.reset_scroll();
+        }
+        StepKind::AddText { params } if !step.background => {
+            // Show the edit first; Enter in the preview applies it.
+            let preview = preview_add_text(params, &app.ctx)
+                .map_err(|e| format!("{:#}", e));
+            app.mode = InteractiveMode::AddTextPreview(preview);
+        }
+        _ => run_current_step(terminal, app)?,
+    }
+    Ok(())
+}
+
+/// Show `path` in `$PAGER` (default `less`) in place of the TUI.
+fn open_in_pager(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    path: &std::path::Path,
+    alt_screen: bool,
+) -> Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    suspend_tui(terminal, true, alt_screen)?;
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", pager))
+        .arg("sh")
+        .arg(path)
+        .status();
+    resume_tui(terminal, true, alt_screen)?;
+    status.with_context(|| format!("Failed to run {}", pager))?;
+    Ok(())
+}
+
+/// The editor to pre-fill in the git form: `$VISUAL`, then `$EDITOR`, then
+/// the step's `default_editor`.
+fn preferred_editor(default_editor: &str) -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| default_editor.to_string())
+}
+
+/// Run the selected step through the executor, outside the TUI, or on a
+/// worker thread for `background` steps.
+fn run_current_step(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+) -> Result<()> {
+    let steps = Rc::clone(&app.steps);
+    let step = &steps[app.current];
+    app.current_runtime_mut().status = StepStatus::Running;
+    if step.background {
+        let rt = app.runtimes[app.current].clone();
+        app.scheduler.spawn(app.current, step.clone(), rt, app.ctx.clone());
+        app.flash = Some(format!("'{}' is running in the background.", step.name));
+        return Ok(());
+    }
+    app.sync_events();
+    suspend_tui(terminal, step.interactive, app.alt_screen)?;
+    let rt = &mut app.runtimes[app.current];
+    let res = run_step(step, rt, &mut app.ctx);
+    resume_tui(terminal, step.interactive, app.alt_screen)?;
+    app.reset_scroll();
+    if let Err(e) = res {
+        let rt = &mut app.runtimes[app.current];
+        rt.status = StepStatus::Failed;
+        rt.log.push_str(&app.ctx.redact(&format!("\n[ERROR] {:#}\n", e)));
+    }
+    Ok(())
+}
+
+/// The `--select` checklist, with every step ticked.
+fn step_selection(app: &App) -> InteractiveMode {
+    InteractiveMode::StepSelection(StepSelectionState {
+        cursor: 0,
+        selected: vec![true; app.steps.len()],
+    })
+}
 
-    // Start sudo at the very beginning.
-    {
-        // Temporarily leave raw mode to let sudo prompt if needed
-        disable_raw_mode()?;
-        let mut dummy_runtime = StepRuntime::default();
-        start_sudo_session(&mut dummy_runtime.log)?;
-        enable_raw_mode()?;
-        app.global_log.push_str(&dummy_runtime.log);
+fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, mut app: App) -> Result<()> {
+    if !matches!(app.mode, InteractiveMode::Intro(_) | InteractiveMode::StepSelection(_)) {
+        start_session(&mut app)?;
     }
 
     loop {
+        app.continue_after_retry();
+        app.pump_batch();
+        app.sync_events();
+        app.clamp_current();
+        app.limit_logs();
+        if app.all_done() {
+            app.notify_finished();
+            if !app.summary_shown && matches!(app.mode, InteractiveMode::None) {
+                app.summary_shown = true;
+                app.mode = InteractiveMode::Summary { cursor: app.current };
+            }
+        }
+        if deadline_reached() {
+            app.global_log.push_str("Global timeout reached; aborting the run.\n");
+            // Workers are killed too; they are not waited for.
+            for rt in &mut app.runtimes {
+                if rt.status == StepStatus::Running {
+                    rt.status = StepStatus::Failed;
+                    rt.log.push_str(&format!("\n[ERROR] {}\n", GlobalTimeout));
+                }
+            }
+            app.mark_not_run();
+            app.write_report(Some("global timeout reached"))?;
+            return Err(GlobalTimeout.into());
+        }
+        app.load_app_size();
+        app.spinner_frame = app.spinner_frame.wrapping_add(1);
         terminal.draw(|f| match &app.mode {
-            InteractiveMode::None => ui(f, &app),
+            _ if too_small(f.area()) => ui_too_small(f),
+            InteractiveMode::None => ui(f, &mut app),
             InteractiveMode::AppSelection(state) => ui_app_selection(f, &app, state),
             InteractiveMode::GitConfig(state) => ui_git_config(f, &app, state),
+            InteractiveMode::Prompt(state) => ui_prompt(f, &app, state),
+            InteractiveMode::AddTextPreview(preview) => ui_add_text_preview(f, &app, preview),
+            InteractiveMode::FailureDetail => ui_failure_detail(f, &app),
+            InteractiveMode::Help => ui_help(f, &app),
+            InteractiveMode::Summary { cursor } => ui_summary(f, &app, *cursor),
+            InteractiveMode::Intro(warnings) => ui_intro(f, &app, warnings),
+            InteractiveMode::ReloadError(error) => ui_reload_error(f, error),
+            InteractiveMode::Explain { text, scroll } => ui_explain(f, &app, text, *scroll),
+            InteractiveMode::Palette(state) => ui_palette(f, &app, state),
+            InteractiveMode::StepSelection(state) => ui_step_selection(f, &app, state),
         })?;
 
-        if event::poll(std::time::Duration::from_millis(250))? {
-            if let Event::Key(KeyEvent { code, .. }) = event::read()? {
+        // A command picked in the palette runs as its key press.
+        let replay = app.replay.take();
+        if replay.is_some() || event::poll(std::time::Duration::from_millis(250))? {
+            let event = match replay {
+                Some(code) => Event::Key(KeyEvent::new(code, KeyModifiers::NONE)),
+                None => event::read()?,
+            };
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event {
+                app.flash = None;
+                // Raw mode turns Ctrl-C into a key press: quit like `q`, from any screen.
+                if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+                    if app.try_quit() {
+                        break;
+                    }
+                    continue;
+                }
                 match &mut app.mode {
                     InteractiveMode::None => match code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Char('n') => {
-                            if app.current + 1 < app.steps.len() {
-                                app.current += 1;
-                                app.reset_scroll();
+                        KeyCode::Char('q') if app.scheduler.running == 0 => {
+                            app.mark_not_run();
+                            app.summary_shown = true;
+                            app.mode = InteractiveMode::Summary { cursor: app.current };
+                        }
+                        KeyCode::Char('q') if app.try_quit() => break,
+                        KeyCode::Char('S') => {
+                            app.mode = InteractiveMode::Summary { cursor: app.current };
+                        }
+                        KeyCode::Char('a') if app.batch => {
+                            app.batch = false;
+                            app.flash =
+                                Some("Batch paused; running steps will finish.".to_string());
+                        }
+                        KeyCode::Char('a') if app.has_not_run() => {
+                            for rt in &mut app.runtimes {
+                                if rt.status == StepStatus::NotRun {
+                                    rt.status = StepStatus::Pending;
+                                }
                             }
+                            app.batch = true;
+                            app.flash = Some("Running all remaining steps...".to_string());
                         }
-                        KeyCode::Char('p') => {
-                            if app.current > 0 {
-                                app.current -= 1;
-                                app.reset_scroll();
+                        KeyCode::Char('a') if app.all_done() => {
+                            app.flash = Some(
+                                "Nothing left to run: every step has finished or was skipped."
+                                    .to_string(),
+                            );
+                        }
+                        KeyCode::Char('a') => {
+                            app.batch = true;
+                            app.flash = Some("Running all remaining steps...".to_string());
+                        }
+                        KeyCode::Char('n') => app.move_cursor(true),
+                        KeyCode::Char('p') => app.move_cursor(false),
+                        KeyCode::Char('h') => {
+                            app.hide_done = !app.hide_done;
+                            app.flash = Some(if app.hide_done {
+                                "Hiding succeeded and skipped steps.".to_string()
+                            } else {
+                                "Showing all steps.".to_string()
+                            });
+                        }
+                        KeyCode::Char('?') => {
+                            app.mode = InteractiveMode::Help;
+                        }
+                        KeyCode::Char('z') => app.full_log = !app.full_log,
+                        KeyCode::Char(':') => {
+                            app.mode = InteractiveMode::Palette(PaletteState::default());
+                        }
+                        KeyCode::Char('<') | KeyCode::Char('>') => {
+                            let split = if code == KeyCode::Char('<') {
+                                app.split.saturating_sub(SPLIT_STEP)
+                            } else {
+                                app.split + SPLIT_STEP
+                            };
+                            app.split = split.clamp(MIN_SPLIT, MAX_SPLIT);
+                            app.flash = Some(format!("Steps pane: {}%", app.split));
+                        }
+                        KeyCode::Char('[') => app.jump_to_first(StepStatus::Failed),
+                        KeyCode::Char(']') => app.jump_to_first(StepStatus::Pending),
+                        KeyCode::Char('d') if app.current_runtime().status == StepStatus::Failed => {
+                            app.mode = InteractiveMode::FailureDetail;
+                        }
+                        KeyCode::Char('Y') => app.copy_failed_command(),
+                        KeyCode::Char('e') => {
+                            let text = explain_step(&app.steps[app.current], &app.ctx);
+                            app.mode = InteractiveMode::Explain { text, scroll: 0 };
+                        }
+                        KeyCode::Enter
+                        | KeyCode::Char('R')
+                        | KeyCode::Char('s')
+                        | KeyCode::Char('m')
+                            if app.visible_indices().is_empty() =>
+                        {
+                            app.flash = Some("No runnable steps (h shows all).".to_string());
+                        }
+                        KeyCode::Char('m') => {
+                            let rt = app.current_runtime_mut();
+                            match rt.status {
+                                StepStatus::Running => {
+                                    app.flash = Some("This step is still running.".to_string());
+                                }
+                                StepStatus::Success if rt.set_by_hand => {
+                                    rt.status = StepStatus::Pending;
+                                    rt.set_by_hand = false;
+                                    rt.log.push_str("Mark undone; step is pending again.\n");
+                                }
+                                _ => {
+                                    rt.status = StepStatus::Success;
+                                    rt.set_by_hand = true;
+                                    rt.log.push_str(
+                                        "Step manually marked as done (press m again to undo).\n",
+                                    );
+                                }
+                            }
+                        }
+                        KeyCode::Char('s')
+                            if app.current_runtime().status == StepStatus::Skipped =>
+                        {
+                            let rt = app.current_runtime_mut();
+                            if rt.set_by_hand {
+                                rt.status = StepStatus::Pending;
+                                rt.set_by_hand = false;
+                                rt.log.push_str("Skip undone; step is pending again.\n");
+                            } else {
+                                app.flash = Some(
+                                    "This step was skipped by its pre_script or run_once; \
+                                     press Enter to run it again."
+                                        .to_string(),
+                                );
                             }
                         }
                         KeyCode::Char('s') => {
                             let rt = app.current_runtime_mut();
                             rt.status = StepStatus::Skipped;
-                            rt.log.push_str("Step manually skipped.\n");
+                            rt.set_by_hand = true;
+                            rt.log.push_str("Step manually skipped (press s again to undo).\n");
                         }
-                        KeyCode::Enter => {
-                            let step = &app.steps[app.current];
-                            match &step.kind {
-                                StepKind::AppSelection { params } => {
-                                    // Enter interactive app selection mode.
-                                    let rt = app.current_runtime_mut();
-                                    rt.status = StepStatus::Running;
-                                    rt.log.push_str(&format!(
-                                        "== Running step: {} (app selection) ==\n",
-                                        step.name
-                                    ));
-
-                                    // Initialise selection state.
-                                    let state = AppSelectionState {
-                                        cursor: 0,
-                                        selected: vec![false; params.apps.len()],
-                                    };
-                                    app.mode = InteractiveMode::AppSelection(state);
-                                    app.reset_scroll();
-                                }
-                                StepKind::GitConfig { params } => {
-                                    let rt = app.current_runtime_mut();
-                                    rt.status = StepStatus::Running;
-                                    rt.log.push_str(&format!(
-                                        "== Running step: {} (git config) ==\n",
-                                        step.name
-                                    ));
-
-                                    let state = GitConfigState {
-                                        field: GitField::Name,
-                                        name: String::new(),
-                                        email: String::new(),
-                                        editor: params.default_editor.clone(),
-                                    };
-                                    app.mode = InteractiveMode::GitConfig(state);
-                                    app.reset_scroll();
-                                }
-                                _ => {
-                                    // Non-interactive steps use the existing executor flow.
-                                    disable_raw_mode()?;
-                                    let rt = app.current_runtime_mut();
-                                    let res = run_step(step, rt);
-                                    enable_raw_mode()?;
-                                    app.reset_scroll();
-                                    if let Err(e) = res {
-                                        let rt = app.current_runtime_mut();
-                                        rt.status = StepStatus::Failed;
-                                        rt.log.push_str(&format!("\n[ERROR] {}\n", e));
+                        KeyCode::Enter | KeyCode::Char('R')
+                            if app.current_runtime().status == StepStatus::Running =>
+                        {
+                            app.flash = Some("This step is already running.".to_string());
+                        }
+                        KeyCode::Enter | KeyCode::Char('R')
+                            if !app.unmet_dependencies(app.current).is_empty() =>
+                        {
+                            app.flash = Some(format!(
+                                "Waiting on: {}",
+                                app.unmet_dependencies(app.current).join(", ")
+                            ));
+                        }
+                        KeyCode::Enter => start_current_step(terminal, &mut app)?,
+                        KeyCode::Char('R') => {
+                            app.current_runtime_mut()
+                                .log
+                                .push_str("\n== Retry, then run all remaining steps ==\n");
+                            app.batch_after_current = true;
+                            start_current_step(terminal, &mut app)?;
+                        }
+
+                        KeyCode::Char('G') => {
+                            app.show_global_log = !app.show_global_log;
+                        }
+                        KeyCode::F(5) if app.scheduler.running > 0 => {
+                            app.flash = Some("Steps are still running; reload once they finish."
+                                .to_string());
+                        }
+                        KeyCode::F(5) => {
+                            if let Err(e) = app.reload() {
+                                app.mode = InteractiveMode::ReloadError(format!("{:#}", e));
+                            }
+                        }
+                        KeyCode::Char('o') => {
+                            match app.spill.as_ref().and_then(|spill| spill.path(app.current)) {
+                                Some(path) => {
+                                    let path = path.clone();
+                                    if let Err(e) = open_in_pager(terminal, &path, app.alt_screen) {
+                                        app.flash = Some(format!("{:#}", e));
                                     }
                                 }
+                                None => {
+                                    app.flash =
+                                        Some("This step's whole log is on screen.".to_string());
+                                }
                             }
                         }
-
                         KeyCode::Up => {
-                            app.log_scroll = app.log_scroll.saturating_sub(1);
+                            let scroll = app.scroll_mut();
+                            *scroll = scroll.saturating_sub(1);
                         }
                         KeyCode::Down => {
-                            app.log_scroll = app.log_scroll.saturating_add(1);
+                            let scroll = app.scroll_mut();
+                            *scroll = scroll.saturating_add(1);
                         }
                         KeyCode::PageUp => {
-                            app.log_scroll = app.log_scroll.saturating_sub(10);
+                            let scroll = app.scroll_mut();
+                            *scroll = scroll.saturating_sub(10);
                         }
                         KeyCode::PageDown => {
-                            app.log_scroll = app.log_scroll.saturating_add(10);
+                            let scroll = app.scroll_mut();
+                            *scroll = scroll.saturating_add(10);
                         }
 
                         _ => {}
                     },
                     InteractiveMode::AppSelection(state) => match code {
+                        _ if state.installing => {
+                            app.flash = Some("Installing; wait for it to finish.".to_string());
+                        }
                         KeyCode::Esc => {
                             // Cancel selection, leave step Pending.
                             let rt = app.current_runtime_mut();
@@ -220,19 +1256,28 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                             app.mode = InteractiveMode::None;
                             app.reset_scroll();
                         }
-                        KeyCode::Up => {
-                            if state.cursor > 0 {
-                                state.cursor -= 1;
+                        KeyCode::Up if state.cursor > 0 => {
+                            state.cursor -= 1;
+                        }
+                        KeyCode::Down if state.cursor + 1 < state.selected.len() => {
+                            state.cursor += 1;
+                        }
+                        KeyCode::Char(' ') if state.cursor < state.selected.len() => {
+                            state.set(state.cursor, !state.selected[state.cursor]);
+                        }
+                        KeyCode::Char('a') => {
+                            for idx in 0..state.selected.len() {
+                                state.set(idx, true);
                             }
                         }
-                        KeyCode::Down => {
-                            if state.cursor + 1 < state.selected.len() {
-                                state.cursor += 1;
+                        KeyCode::Char('A') => {
+                            for idx in 0..state.selected.len() {
+                                state.set(idx, false);
                             }
                         }
-                        KeyCode::Char(' ') => {
-                            if let Some(sel) = state.selected.get_mut(state.cursor) {
-                                *sel = !*sel;
+                        KeyCode::Char('i') => {
+                            for idx in 0..state.selected.len() {
+                                state.set(idx, !state.selected[idx]);
                             }
                         }
                         KeyCode::Enter => {
@@ -240,18 +1285,57 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                             let step_index = app.current;
                             let step = &app.steps[step_index];
                             if let StepKind::AppSelection { params } = &step.kind {
-                                let selected_indices: Vec<usize> = state
-                                    .selected
-                                    .iter()
-                                    .enumerate()
-                                    .filter_map(|(idx, &sel)| if sel { Some(idx) } else { None })
-                                    .collect();
+                                let selected_indices: Vec<usize> = match params.install_order {
+                                    InstallOrder::Selection => state.order.clone(),
+                                    InstallOrder::Declaration => state
+                                        .selected
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|(_, &sel)| sel)
+                                        .map(|(idx, _)| idx)
+                                        .collect(),
+                                };
 
-                                let rt = app.current_runtime_mut();
-                                if let Err(e) = apply_app_selection(params, &selected_indices, &mut rt.log)
-                                {
+                                // Without the terminal, install on a worker so the
+                                // checklist can show the progress.
+                                if !step.interactive {
+                                    let params = params.clone();
+                                    let step = step.clone();
+                                    app.scheduler.spawn_job(
+                                        step_index,
+                                        app.runtimes[step_index].clone(),
+                                        app.ctx.clone(),
+                                        move |rt, ctx| {
+                                            apply_app_selection(
+                                                &params,
+                                                &selected_indices,
+                                                &step,
+                                                rt,
+                                                ctx,
+                                            )?;
+                                            if rt.status == StepStatus::Running {
+                                                rt.status = StepStatus::Success;
+                                            }
+                                            Ok(())
+                                        },
+                                    );
+                                    state.installing = true;
+                                    continue;
+                                }
+                                suspend_tui(terminal, true, app.alt_screen)?;
+                                let rt = &mut app.runtimes[step_index];
+                                let res = apply_app_selection(
+                                    params,
+                                    &selected_indices,
+                                    step,
+                                    rt,
+                                    &app.ctx,
+                                );
+                                resume_tui(terminal, true, app.alt_screen)?;
+                                if let Err(e) = res {
                                     rt.status = StepStatus::Failed;
-                                    rt.log.push_str(&format!("\n[ERROR] {}\n", e));
+                                    let error = format!("\n[ERROR] {:#}\n", e);
+                                    rt.log.push_str(&app.ctx.redact(&error));
                                 } else if rt.status == StepStatus::Running {
                                     rt.status = StepStatus::Success;
                                 }
@@ -272,6 +1356,17 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                         let mut action = GitAction::None;
 
                         match code {
+                            KeyCode::Enter | KeyCode::Char('y') if state.confirm => {
+                                action = GitAction::Apply {
+                                    name: state.name.clone(),
+                                    email: state.email.clone(),
+                                    editor: state.editor.clone(),
+                                };
+                            }
+                            KeyCode::Esc | KeyCode::Char('n') if state.confirm => {
+                                state.confirm = false;
+                            }
+                            _ if state.confirm => {}
                             KeyCode::Esc => {
                                 action = GitAction::Cancel;
                             }
@@ -306,11 +1401,19 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                                 buf.push(c);
                             }
                             KeyCode::Enter => {
-                                action = GitAction::Apply {
-                                    name: state.name.clone(),
-                                    email: state.email.clone(),
-                                    editor: state.editor.clone(),
-                                };
+                                // Only once the values are complete; the form
+                                // shows what is missing.
+                                let kind = &app.steps[app.current].kind;
+                                if let StepKind::GitConfig { params } = kind {
+                                    state.confirm = git_config_commands(
+                                        params,
+                                        &state.name,
+                                        &state.email,
+                                        &state.editor,
+                                        app.ctx.strict,
+                                    )
+                                    .is_ok();
+                                }
                             }
                             _ => {}
                         }
@@ -326,19 +1429,22 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                                 app.reset_scroll();
                             }
                             GitAction::Apply { name, email, editor } => {
-                                let step_index = app.current;
-                                let step = &app.steps[step_index];
+                                let steps = Rc::clone(&app.steps);
+                                let step = &steps[app.current];
                                 if let StepKind::GitConfig { params } = &step.kind {
-                                    let rt = app.current_runtime_mut();
+                                    let strict = app.ctx.strict;
+                                    let rt = &mut app.runtimes[app.current];
                                     if let Err(e) = apply_git_config(
                                         params,
                                         &name,
                                         &email,
                                         &editor,
-                                        &mut rt.log,
+                                        strict,
+                                        rt,
                                     ) {
                                         rt.status = StepStatus::Failed;
-                                        rt.log.push_str(&format!("\n[ERROR] {}\n", e));
+                                        let error = format!("\n[ERROR] {:#}\n", e);
+                                        rt.log.push_str(&app.ctx.redact(&error));
                                     } else if rt.status == StepStatus::Running {
                                         rt.status = StepStatus::Success;
                                     }
@@ -349,15 +1455,194 @@ fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, steps: &[Step]) -> Res
                             }
                         }
                     },
+                    InteractiveMode::Intro(_) => match code {
+                        KeyCode::Char('q') | KeyCode::Esc => break,
+                        KeyCode::Enter if app.select_steps => app.mode = step_selection(&app),
+                        KeyCode::Enter => {
+                            app.mode = InteractiveMode::None;
+                            start_session(&mut app)?;
+                        }
+                        _ => {}
+                    },
+                    InteractiveMode::StepSelection(state) => match code {
+                        KeyCode::Char('q') | KeyCode::Esc => break,
+                        KeyCode::Up if state.cursor > 0 => state.cursor -= 1,
+                        KeyCode::Down if state.cursor + 1 < state.selected.len() => {
+                            state.cursor += 1;
+                        }
+                        KeyCode::Char(' ') => {
+                            if let Some(on) = state.selected.get_mut(state.cursor) {
+                                *on = !*on;
+                            }
+                        }
+                        KeyCode::Char('a') => state.selected.iter_mut().for_each(|on| *on = true),
+                        KeyCode::Char('A') => state.selected.iter_mut().for_each(|on| *on = false),
+                        KeyCode::Char('i') => state.selected.iter_mut().for_each(|on| *on = !*on),
+                        KeyCode::Enter => {
+                            let selected = state.selected.clone();
+                            app.apply_step_selection(&selected);
+                            app.mode = InteractiveMode::None;
+                            start_session(&mut app)?;
+                        }
+                        _ => {}
+                    },
+                    InteractiveMode::Summary { cursor } => {
+                        let cursor = *cursor;
+                        match code {
+                            KeyCode::Char('q') if app.try_quit() => break,
+                            KeyCode::Up => {
+                                app.mode = InteractiveMode::Summary {
+                                    cursor: cursor.saturating_sub(1),
+                                };
+                            }
+                            KeyCode::Down if cursor + 1 < app.steps.len() => {
+                                app.mode = InteractiveMode::Summary { cursor: cursor + 1 };
+                            }
+                            KeyCode::Enter => {
+                                app.current = cursor;
+                                app.reset_scroll();
+                                // A finished step is hidden by `h`; show it anyway.
+                                if !app.visible_indices().contains(&cursor) {
+                                    app.hide_done = false;
+                                }
+                                app.show_global_log = false;
+                                app.mode = InteractiveMode::None;
+                            }
+                            KeyCode::Esc => app.mode = InteractiveMode::None,
+                            _ => {}
+                        }
+                    }
+                    InteractiveMode::Help => {
+                        // Any key closes the overlay.
+                        app.mode = InteractiveMode::None;
+                    }
+                    InteractiveMode::AddTextPreview(_) => match code {
+                        KeyCode::Enter => {
+                            app.mode = InteractiveMode::None;
+                            run_current_step(terminal, &mut app)?;
+                        }
+                        KeyCode::Esc => {
+                            app.mode = InteractiveMode::None;
+                            app.flash = Some("add_text cancelled; step left pending.".to_string());
+                        }
+                        _ => {}
+                    },
+                    InteractiveMode::FailureDetail => match code {
+                        KeyCode::Esc => app.mode = InteractiveMode::None,
+                        KeyCode::Char('Y') => app.copy_failed_command(),
+                        _ => {}
+                    },
+                    InteractiveMode::ReloadError(_) => {
+                        app.mode = InteractiveMode::None;
+                    }
+                    InteractiveMode::Palette(state) => match code {
+                        KeyCode::Esc => app.mode = InteractiveMode::None,
+                        KeyCode::Up => state.cursor = state.cursor.saturating_sub(1),
+                        KeyCode::Down if state.cursor + 1 < state.matches().len() => {
+                            state.cursor += 1;
+                        }
+                        KeyCode::Backspace => {
+                            state.query.pop();
+                            state.cursor = 0;
+                        }
+                        KeyCode::Char(c) => {
+                            state.query.push(c);
+                            state.cursor = 0;
+                        }
+                        KeyCode::Enter => {
+                            if let Some(&idx) = state.matches().get(state.cursor) {
+                                app.replay = Some(PALETTE_COMMANDS[idx].2);
+                            }
+                            app.mode = InteractiveMode::None;
+                        }
+                        _ => {}
+                    },
+                    InteractiveMode::Explain { scroll, .. } => match code {
+                        KeyCode::Esc | KeyCode::Char('e') => app.mode = InteractiveMode::None,
+                        KeyCode::Up => *scroll = scroll.saturating_sub(1),
+                        KeyCode::Down => *scroll = scroll.saturating_add(1),
+                        KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                        KeyCode::PageDown => *scroll = scroll.saturating_add(10),
+                        _ => {}
+                    },
+                    InteractiveMode::Prompt(state) => {
+                        enum PromptAction {
+                            None,
+                            Cancel,
+                            Apply(Vec<String>),
+                        }
+
+                        let mut action = PromptAction::None;
+                        let field_count = state.values.len();
+
+                        match code {
+                            KeyCode::Esc => {
+                                action = PromptAction::Cancel;
+                            }
+                            KeyCode::Tab => {
+                                state.field = (state.field + 1) % field_count;
+                            }
+                            KeyCode::BackTab => {
+                                state.field = (state.field + field_count - 1) % field_count;
+                            }
+                            KeyCode::Backspace => {
+                                if let Some(buf) = state.values.get_mut(state.field) {
+                                    buf.pop();
+                                }
+                            }
+                            KeyCode::Char(c) => {
+                                if let Some(buf) = state.values.get_mut(state.field) {
+                                    buf.push(c);
+                                }
+                            }
+                            KeyCode::Enter => {
+                                action = PromptAction::Apply(state.values.clone());
+                            }
+                            _ => {}
+                        }
+
+                        match action {
+                            PromptAction::None => {}
+                            PromptAction::Cancel => {
+                                let rt = app.current_runtime_mut();
+                                rt.status = StepStatus::Pending;
+                                rt.log.push_str("Prompt cancelled.\n");
+                                app.mode = InteractiveMode::None;
+                                app.reset_scroll();
+                            }
+                            PromptAction::Apply(values) => {
+                                let step = &app.steps[app.current];
+                                if let StepKind::Prompt { params } = &step.kind {
+                                    let rt = &mut app.runtimes[app.current];
+                                    apply_prompt(params, values, rt, &mut app.ctx);
+                                }
+
+                                app.mode = InteractiveMode::None;
+                                app.reset_scroll();
+                            }
+                        }
+                    }
                 }
             }
         }
     }
 
-    Ok(())
+    app.write_report(None)
 }
 
-fn ui(f: &mut ratatui::Frame<>, app: &App) {
+/// Width of the widest kind tag (`[apps]`, `[edit]`), so names line up.
+const TAG_WIDTH: usize = 6;
+
+/// Foreground `color`, or the terminal's default style when colours are off.
+fn fg(color: bool, value: Color) -> Style {
+    if color {
+        Style::default().fg(value)
+    } else {
+        Style::default()
+    }
+}
+
+fn ui(f: &mut ratatui::Frame<>, app: &mut App) {
     let size = f.area();
 
     // NEW: split vertically into main body + 1-line status bar
@@ -378,9 +1663,101 @@ fn ui(f: &mut ratatui::Frame<>, app: &App) {
     // Existing layout now applied to body_area
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)].as_ref())
+        .constraints(
+            [Constraint::Percentage(app.split), Constraint::Percentage(100 - app.split)].as_ref(),
+        )
         .split(body_area);
 
+    let log_area = if app.full_log {
+        body_area
+    } else {
+        ui_steps_pane(f, app, chunks[0]);
+        chunks[1]
+    };
+
+    // Log of the current step (or the global log), with scroll: the right side,
+    // or the whole body with `z`.
+    let (log, title, scroll) = if app.show_global_log {
+        (app.global_log.clone(), "Global log".to_string(), app.global_log_scroll)
+    } else {
+        (step_log(app), format!("Step log: {}", app.steps[app.current].name), app.log_scroll)
+    };
+    let log_widget = Paragraph::new(log)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .scroll((scroll, 0));
+
+    f.render_widget(log_widget, log_area);
+
+    // NEW: status bar at the bottom
+    let total = app.steps.len();
+    let current_idx = app.current + 1;
+    let status = app.current_runtime().status;
+    let current_status = status.label();
+    let not_run = app
+        .runtimes
+        .iter()
+        .filter(|rt| matches!(rt.status, StepStatus::Pending | StepStatus::NotRun))
+        .count();
+
+    let spinner = if status == StepStatus::Running {
+        format!(" {}", app.spinner())
+    } else {
+        String::new()
+    };
+
+    let mut status_text = Line::from(vec![
+        Span::raw(format!(" Step {}/{} ", current_idx, total)),
+        Span::raw("| "),
+        Span::styled(
+            format!("Status: {}{}", current_status, spinner),
+            fg(app.color, app.theme.status(status).color),
+        ),
+        Span::raw(format!(" | {} not run", not_run)),
+    ]);
+    let attempts = app.current_runtime().attempt_note();
+    if !attempts.is_empty() {
+        status_text.push_span(Span::raw(format!(" | {}", attempts)));
+    }
+    if status == StepStatus::Running {
+        if let Some(cmd) = app.current_runtime().current_command() {
+            let cmd = shorten(&cmd, CURRENT_COMMAND_WIDTH);
+            status_text.push_span(Span::raw(format!(" | $ {}", cmd)));
+        }
+    }
+    if app.batch || app.scheduler.running > 0 {
+        status_text.push_span(Span::raw(format!(
+            " | batch: {}/{} running",
+            app.scheduler.running, app.scheduler.jobs
+        )));
+    }
+    status_text.extend([
+        Span::raw(" | "),
+        Span::raw("Press 'q' to quit."),
+    ]);
+    if let Some(flash) = &app.flash {
+        status_text.push_span(Span::styled(
+            format!(" | {}", flash),
+            fg(app.color, app.theme.accent),
+        ));
+    }
+
+    let status = Paragraph::new(status_text);
+    f.render_widget(status, status_area);
+}
+
+/// Log of the selected step; while a worker runs it, what it has logged so far.
+fn step_log(app: &App) -> String {
+    let rt = app.current_runtime();
+    let live = (rt.status == StepStatus::Running).then(|| rt.live_log());
+    match live.filter(|live| !live.is_empty()) {
+        Some(live) => app.ctx.redact(&live),
+        None => rt.log.clone(),
+    }
+}
+
+/// The steps list and the help box below it.
+fn ui_steps_pane(f: &mut ratatui::Frame<>, app: &mut App, area: Rect) {
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -390,84 +1767,156 @@ fn ui(f: &mut ratatui::Frame<>, app: &App) {
             ]
             .as_ref(),
         )
-        .split(chunks[0]);
+        .split(area);
 
     // Steps list.
-    let items: Vec<ListItem> = app
-        .steps
+    let visible = app.visible_indices();
+    let mut items: Vec<ListItem> = visible
         .iter()
-        .enumerate()
-        .map(|(idx, step)| {
+        .map(|&idx| {
+            let step = &app.steps[idx];
             let rt = &app.runtimes[idx];
-            let status_str = match rt.status {
-                StepStatus::Pending => "[ ]",
-                StepStatus::Running => "[>]",
-                StepStatus::Skipped => "[-]",
-                StepStatus::Success => "[✓]",
-                StepStatus::Failed => "[✗]",
-            };
-            let prefix = if idx == app.current { "➤" } else { " " };
-            let content = format!("{} {} {}", prefix, status_str, step.name);
-            ListItem::new(content)
+            let status_str = pad(&app.theme.status(rt.status).symbol, app.theme.symbol_width());
+            let prefix = if idx == app.current { app.theme.cursor.as_str() } else { "" };
+            let prefix = pad(prefix, app.theme.cursor.width().max(1));
+            let tag = format!("{:<width$}", format!("[{}]", step.kind.tag()), width = TAG_WIDTH);
+            let mut spans = vec![
+                Span::raw(format!("{} {} ", prefix, status_str)),
+                Span::styled(tag, fg(app.color, app.theme.tag(&step.kind))),
+                Span::raw(format!(" {}", step.name)),
+            ];
+            let attempts = rt.attempt_note();
+            if !attempts.is_empty() {
+                spans.push(Span::raw(format!(" {}", attempts)));
+            }
+            if rt.status == StepStatus::Running {
+                spans.push(Span::raw(format!(" {}", app.spinner())));
+            }
+            ListItem::new(Line::from(spans))
         })
         .collect();
+    let placeholder = if visible.is_empty() {
+        Some("  No runnable steps (h shows all)")
+    } else if app.all_done() {
+        Some("  No steps left to run")
+    } else {
+        None
+    };
+    if let Some(text) = placeholder {
+        items.push(ListItem::new(Line::from(Span::styled(text, fg(app.color, app.theme.muted)))));
+    }
 
+    let title = match app.steps.len() - visible.len() {
+        0 => "Steps".to_string(),
+        hidden => format!("Steps ({} done hidden)", hidden),
+    };
     let steps_list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Steps"));
+        .block(Block::default().borders(Borders::ALL).title(title));
 
-    f.render_widget(steps_list, left_chunks[0]);
+    app.list_state.select(visible.iter().position(|&idx| idx == app.current));
+    f.render_stateful_widget(steps_list, left_chunks[0], &mut app.list_state);
 
     // Help box.
     let help = Paragraph::new(
-        "Keys: Enter=Run | n=Next | p=Prev | s=Skip | Up/Down/PgUp/PgDn=Scroll | q=Quit"
+        "Keys: Enter=Run | a=Run all | n=Next | p=Prev | s=Skip | q=Quit | ?=All keys"
     )
     .block(Block::default().borders(Borders::ALL).title("Help"));
 
     f.render_widget(help, left_chunks[1]);
+}
 
-    // Right side: log of current step, with scroll.
-    let log = &app.current_runtime().log;
-    let log_widget = Paragraph::new(log.clone())
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(format!("Step log: {}", app.steps[app.current].name)),
-        )
-        .wrap(ratatui::widgets::Wrap { trim: false })
-        .scroll((app.log_scroll, 0)); // NEW: apply scroll
+/// Indices of the steps shown in the list: all of them, or with `hide_done`
+/// only those that have not succeeded or been skipped.
+fn visible_rows(runtimes: &[StepRuntime], hide_done: bool) -> Vec<usize> {
+    runtimes
+        .iter()
+        .enumerate()
+        .filter(|(_, rt)| {
+            !hide_done || !matches!(rt.status, StepStatus::Success | StepStatus::Skipped)
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
 
-    f.render_widget(log_widget, chunks[1]);
+/// `current` if it is visible, else the next visible step, else the last one.
+/// With nothing visible the cursor stays put.
+fn clamp_to_visible(visible: &[usize], current: usize) -> usize {
+    if visible.is_empty() || visible.contains(&current) {
+        return current;
+    }
+    visible
+        .iter()
+        .copied()
+        .find(|&idx| idx > current)
+        .or_else(|| visible.last().copied())
+        .unwrap_or(current)
+}
 
-    // NEW: status bar at the bottom
-    let total = app.steps.len();
-    let current_idx = app.current + 1;
-    let current_status = match app.current_runtime().status {
-        StepStatus::Pending => "Pending",
-        StepStatus::Running => "Running",
-        StepStatus::Skipped => "Skipped",
-        StepStatus::Success => "Success",
-        StepStatus::Failed => "Failed",
-    };
+/// Longest command shown in the status bar while a step runs.
+const CURRENT_COMMAND_WIDTH: usize = 40;
 
-    let status_text = Line::from(vec![
-        Span::raw(format!(" Step {}/{} ", current_idx, total)),
-        Span::raw("| "),
-        Span::styled(
-            format!("Status: {}", current_status),
-            Style::default().fg(match current_status {
-                "Success" => Color::Green,
-                "Failed" => Color::Red,
-                "Running" => Color::Yellow,
-                "Skipped" => Color::Blue,
-                _ => Color::White,
-            }),
-        ),
-        Span::raw(" | "),
-        Span::raw("Press 'q' to quit."),
-    ]);
+/// First line of `text`, cut to `max` columns with a trailing `…`.
+fn shorten(text: &str, max: usize) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.width() <= max && !text.trim().contains('\n') {
+        return line.to_string();
+    }
+    format!("{}…", take_width(line, max.saturating_sub(1)))
+}
 
-    let status = Paragraph::new(status_text);
-    f.render_widget(status, status_area);
+/// The longest start of `text` that fits in `max` terminal columns. Wide
+/// (e.g. CJK) characters take two columns, combining marks none.
+fn take_width(text: &str, max: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > max {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// `text` followed by enough spaces to fill `width` columns.
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+/// `line` split into pieces of at most `width` columns (at least one
+/// character each, even if that is wider).
+fn wrap_width(line: &str, width: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let mut piece = take_width(rest, width);
+        if piece.is_empty() {
+            let first = rest.chars().next().map_or(0, char::len_utf8);
+            piece = &rest[..first];
+        }
+        pieces.push(piece);
+        rest = &rest[piece.len()..];
+    }
+    pieces
+}
+
+/// Install commands get at least this many columns in the app checklist,
+/// however long the app's name and notes are.
+const MIN_COMMAND_WIDTH: usize = 12;
+
+/// `cmd` on one line (multi-line scripts joined with `; `), cut to `max`
+/// columns with an ellipsis. The cut is made at the last space when that
+/// keeps most of it, so it does not fall in the middle of a word.
+fn truncate_command(cmd: &str, max: usize) -> String {
+    let cmd = cmd.trim().lines().map(str::trim).collect::<Vec<_>>().join("; ");
+    if cmd.width() <= max {
+        return cmd;
+    }
+    let kept = take_width(&cmd, max.saturating_sub(1));
+    let kept = match kept.rfind(' ') {
+        Some(space) if kept[..space].width() >= kept.width() / 2 => kept[..space].trim_end(),
+        _ => kept,
+    };
+    format!("{}…", kept)
 }
 
 fn ui_app_selection(
@@ -477,19 +1926,19 @@ fn ui_app_selection(
 ) {
     let size = f.area();
 
+    let mut constraints = vec![Constraint::Min(5)]; // top: interactive selection
+    if state.installing {
+        constraints.push(Constraint::Length(3)); // install progress
+    }
+    constraints.push(Constraint::Percentage(50)); // bottom: log
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Min(5),      // top: interactive selection
-                Constraint::Percentage(50), // bottom: log
-            ]
-            .as_ref(),
-        )
+        .constraints(constraints)
         .split(size);
 
     // Top: checklist of apps for the current AppSelection step.
     let step = &app.steps[app.current];
+    let width = chunks[0].width.saturating_sub(2) as usize;
     let items: Vec<ListItem> = if let StepKind::AppSelection { params } = &step.kind {
         params
             .apps
@@ -501,13 +1950,39 @@ fn ui_app_selection(
                     .get(idx)
                     .copied()
                     .unwrap_or(false);
-                let mark = if checked { "[x]" } else { "[ ]" };
-                let cursor = if idx == state.cursor { "➤" } else { " " };
-                let text = format!(
-                    "{} {} {} ({}) - {}",
-                    cursor, mark, app_def.name, app_def.version, app_def.install
-                );
-                ListItem::new(text)
+                let position = state.order.iter().position(|&i| i == idx);
+                let mark = match (checked, params.install_order, position) {
+                    (true, InstallOrder::Selection, Some(pos)) => format!("[{}]", pos + 1),
+                    (true, _, _) => "[x]".to_string(),
+                    (false, _, _) => "[ ]".to_string(),
+                };
+                let focused = idx == state.cursor;
+                let cursor = if focused { app.theme.cursor.as_str() } else { " " };
+                let head = format!("{} {} {} ({}) - ", cursor, mark, app_def.name, app_def.version);
+                let mut tail = String::new();
+                let size = state.sizes.get(idx).and_then(|size| size.as_deref());
+                for note in [app_def.meta.as_deref(), size].into_iter().flatten() {
+                    if !note.is_empty() {
+                        tail.push_str(&format!(" | {}", note));
+                    }
+                }
+                let install = params.install_command(app_def).unwrap_or_default();
+                let room = width
+                    .saturating_sub(head.width() + tail.width())
+                    .max(MIN_COMMAND_WIDTH);
+                let short = truncate_command(&install, room);
+                let mut lines = vec![Line::from(format!("{}{}{}", head, short, tail))];
+                // The focused app also gets its whole command, wrapped.
+                if focused && short != install.trim() {
+                    let indent = "      ";
+                    let per_line = width.saturating_sub(indent.len()).max(1);
+                    for line in install.trim().lines() {
+                        for piece in wrap_width(line, per_line) {
+                            lines.push(Line::from(format!("{}{}", indent, piece)));
+                        }
+                    }
+                }
+                ListItem::new(lines)
             })
             .collect()
     } else {
@@ -517,14 +1992,17 @@ fn ui_app_selection(
     let list = List::new(items).block(
         Block::default()
             .borders(Borders::ALL)
-            .title("Select apps (Space=toggle, Enter=confirm, Esc=cancel)"),
+            .title("Select apps (Space=toggle, a=all, A=none, i=invert, Enter=ok, Esc=cancel)"),
     );
 
     f.render_widget(list, chunks[0]);
 
+    if state.installing {
+        f.render_widget(install_gauge(app, chunks[1].width), chunks[1]);
+    }
+
     // Bottom: log for current step.
-    let log = &app.current_runtime().log;
-    let log_widget = Paragraph::new(log.clone())
+    let log_widget = Paragraph::new(step_log(app))
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -532,7 +2010,35 @@ fn ui_app_selection(
         )
         .wrap(ratatui::widgets::Wrap { trim: false });
 
-    f.render_widget(log_widget, chunks[1]);
+    f.render_widget(log_widget, chunks[chunks.len() - 1]);
+}
+
+/// `k/N installed` for the selected step's running installs, red once one
+/// has failed, with the command being run in the title.
+fn install_gauge(app: &App, width: u16) -> Gauge<'static> {
+    let rt = app.current_runtime();
+    let progress = rt.installs();
+    let ratio = match progress.total {
+        0 => 0.0,
+        total => (progress.done as f64 / total as f64).min(1.0),
+    };
+    let mut label = format!("{}/{} installed", progress.done, progress.total);
+    if progress.failed > 0 {
+        label.push_str(&format!(", {} failed", progress.failed));
+    }
+    let color = if progress.failed > 0 { Color::Red } else { Color::Green };
+    let title = match rt.current_command() {
+        Some(cmd) => {
+            let room = usize::from(width).saturating_sub(" Installing:  ".len() + 2);
+            format!("Installing: {}", truncate_command(&cmd, room))
+        }
+        None => "Installing".to_string(),
+    };
+    Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .gauge_style(fg(app.color, color))
+        .ratio(ratio)
+        .label(label)
 }
 
 fn ui_git_config(
@@ -567,25 +2073,38 @@ fn ui_git_config(
     let email = &state.email;
     let editor = &state.editor;
 
-    let preview = format!(
-        "Git configuration (Tab/Shift+Tab to move, type to edit, Enter=apply, Esc=cancel)\n\
+    let header = if state.confirm {
+        "These commands change your global git config. Enter/y=apply, Esc/n=back to editing"
+    } else {
+        "Git configuration (Tab/Shift+Tab to move, type to edit, Enter=review, Esc=cancel)"
+    };
+    let mut preview = format!(
+        "{}\n\
 {} user.name: {}\n\
 {} user.email: {}\n\
-{} editor: {}\n\n\
-Commands to run:\n\
-  git config --global user.name '{}'\n\
-  git config --global user.email '{}'\n\
-  git config --global core.editor '{}'\n",
+{} editor: {}\n\n",
+        header,
         field_marker(GitField::Name),
         name,
         field_marker(GitField::Email),
         email,
         field_marker(GitField::Editor),
         editor,
-        name.replace('\'', "\\'"),
-        email.replace('\'', "\\'"),
-        editor.replace('\'', "\\'"),
     );
+    if let StepKind::GitConfig { params } = &step.kind {
+        match git_config_commands(params, name, email, editor, app.ctx.strict) {
+            Ok(commands) => {
+                preview.push_str("Commands to run:\n");
+                for cmd in commands {
+                    preview.push_str(&format!("  {}\n", cmd));
+                }
+                if let Some(problem) = missing_editor(git_editor(params, editor)) {
+                    preview.push_str(&format!("Warning: {}\n", problem));
+                }
+            }
+            Err(e) => preview.push_str(&format!("Not ready: {:#}\n", e)),
+        }
+    }
 
     let top = Paragraph::new(preview).block(
         Block::default()
@@ -607,3 +2126,482 @@ Commands to run:\n\
 
     f.render_widget(log_widget, chunks[1]);
 }
+
+/// Lines of unchanged file shown around an `add_text` insertion.
+const PREVIEW_CONTEXT: usize = 3;
+
+fn ui_add_text_preview(
+    f: &mut ratatui::Frame<>,
+    app: &App,
+    preview: &Result<AddTextPreview, String>,
+) {
+    let step = &app.steps[app.current];
+    let mut lines = Vec::new();
+    match preview {
+        Ok(preview) => {
+            lines.push(Line::from(format!("{} {}", preview.description, preview.file)));
+            lines.push(Line::from(""));
+            let start = preview.inserted.start.saturating_sub(PREVIEW_CONTEXT);
+            let end = (preview.inserted.end + PREVIEW_CONTEXT).min(preview.lines.len());
+            if start > 0 {
+                lines.push(Line::from(Span::styled(
+                    format!("  ... {} line(s) above", start),
+                    fg(app.color, app.theme.muted),
+                )));
+            }
+            for (idx, text) in preview.lines[start..end].iter().enumerate() {
+                let idx = start + idx;
+                let line = if preview.inserted.contains(&idx) {
+                    Line::from(Span::styled(
+                        format!("{:>4} + {}", idx + 1, text),
+                        fg(app.color, Color::Green),
+                    ))
+                } else {
+                    Line::from(format!("{:>4}   {}", idx + 1, text))
+                };
+                lines.push(line);
+            }
+            if end < preview.lines.len() {
+                lines.push(Line::from(Span::styled(
+                    format!("  ... {} line(s) below", preview.lines.len() - end),
+                    fg(app.color, app.theme.muted),
+                )));
+            }
+        }
+        Err(e) => {
+            lines.push(Line::from(Span::styled(
+                format!("This edit cannot be applied: {}", e),
+                fg(app.color, Color::Red),
+            )));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("Enter=apply | Esc=cancel (step stays pending)"));
+
+    let panel = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Preview: {}", step.name)),
+        )
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    f.render_widget(panel, f.area());
+}
+
+fn ui_prompt(
+    f: &mut ratatui::Frame<>,
+    app: &App,
+    state: &PromptState,
+) {
+    let size = f.area();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(5),      // top: interactive fields
+                Constraint::Percentage(50), // bottom: log
+            ]
+            .as_ref(),
+        )
+        .split(size);
+
+    let step = &app.steps[app.current];
+
+    let mut text = String::from(
+        "Fill in the values (Tab/Shift+Tab to move, type to edit, Enter=apply, Esc=cancel)\n",
+    );
+    if let StepKind::Prompt { params } = &step.kind {
+        for (idx, field) in params.fields.iter().enumerate() {
+            let marker = if idx == state.field { ">" } else { " " };
+            let value = state.values.get(idx).map(String::as_str).unwrap_or("");
+            let value = if field.secret {
+                "•".repeat(value.chars().count())
+            } else {
+                value.to_string()
+            };
+            text.push_str(&format!(
+                "{} {} (${{{}}}): {}\n",
+                marker, field.label, field.var, value
+            ));
+        }
+    }
+
+    let top = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Interactive prompt"),
+    );
+
+    f.render_widget(top, chunks[0]);
+
+    // Bottom: log for current step.
+    let log = &app.current_runtime().log;
+    let log_widget = Paragraph::new(log.clone())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Step log: {}", step.name)),
+        )
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    f.render_widget(log_widget, chunks[1]);
+}
+
+/// Smallest terminal the layouts are drawn in; below it only a note is shown.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 12;
+
+fn too_small(area: Rect) -> bool {
+    area.width < MIN_WIDTH || area.height < MIN_HEIGHT
+}
+
+fn ui_too_small(f: &mut ratatui::Frame<>) {
+    let area = f.area();
+    let text = format!(
+        "Terminal too small ({}x{}); need at least {}x{}.",
+        area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+    );
+    let note = Paragraph::new(text).wrap(ratatui::widgets::Wrap { trim: true });
+    f.render_widget(note, area);
+}
+
+fn ui_reload_error(f: &mut ratatui::Frame<>, error: &str) {
+    let text = format!(
+        "{}\n\nThe steps and their progress were left as they were. Press any key to go back.",
+        error
+    );
+    let panel = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title("Reload failed"))
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    f.render_widget(panel, f.area());
+}
+
+fn ui_palette(f: &mut ratatui::Frame<>, app: &App, state: &PaletteState) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)])
+        .split(f.area());
+
+    let input = Paragraph::new(format!(":{}", state.query)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Command palette (type to filter, Up/Down=select, Enter=run, Esc=close)"),
+    );
+    f.render_widget(input, chunks[0]);
+
+    let matches = state.matches();
+    let items: Vec<ListItem> = matches
+        .iter()
+        .enumerate()
+        .map(|(pos, &idx)| {
+            let (name, key, _) = PALETTE_COMMANDS[idx];
+            let cursor = if pos == state.cursor { app.theme.cursor.as_str() } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} {} ", pad(cursor, app.theme.cursor.width().max(1)), name)),
+                Span::styled(format!("({})", key), fg(app.color, app.theme.muted)),
+            ]))
+        })
+        .collect();
+    let title = match matches.len() {
+        0 => "No matching command".to_string(),
+        n => format!("{} command(s)", n),
+    };
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    let mut list_state = ListState::default().with_selected(Some(state.cursor));
+    f.render_stateful_widget(list, chunks[1], &mut list_state);
+}
+
+fn ui_explain(f: &mut ratatui::Frame<>, app: &App, text: &str, scroll: u16) {
+    let title = format!(
+        "What '{}' would do (nothing has run; Up/Down=scroll, Esc=back)",
+        app.steps[app.current].name
+    );
+    let panel = Paragraph::new(text.to_string())
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .scroll((scroll, 0));
+
+    f.render_widget(panel, f.area());
+}
+
+fn ui_failure_detail(f: &mut ratatui::Frame<>, app: &App) {
+    let step = &app.steps[app.current];
+    let rt = app.current_runtime();
+
+    let text = match rt.last_failed_command() {
+        Some(record) => {
+            let stderr = if record.stderr.trim().is_empty() {
+                "<empty>"
+            } else {
+                record.stderr.as_str()
+            };
+            format!(
+                "Command:\n  {}\n\nExit code: {}\n\nstderr:\n{}",
+                record.command, record.exit_code, stderr
+            )
+        }
+        None => "No failing command was recorded for this step; see the step log for the error."
+            .to_string(),
+    };
+
+    let panel = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Failure details: {} (Y=copy command, Esc=back)", step.name)),
+        )
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    f.render_widget(panel, f.area());
+}
+
+/// Key bindings shown in the help overlay, grouped by context.
+const HELP_SECTIONS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Main view",
+        &[
+            ("Enter", "Run the selected step"),
+            ("n / p", "Next / previous step"),
+            ("h", "Hide / show succeeded and skipped steps"),
+            ("s", "Skip the selected step, or undo your skip"),
+            ("m", "Mark the selected step as done without running it, or undo"),
+            ("a", "Run all remaining steps / pause that batch"),
+            ("R", "Retry the selected step, then run all remaining steps"),
+            ("d", "Failure details of a failed step"),
+            ("e", "Explain what the selected step would do, without running it"),
+            ("Y", "Copy the failing command to the clipboard"),
+            ("[ / ]", "Jump to the first failed / pending step"),
+            ("G", "Toggle between the step log and the global log"),
+            ("z", "Show the log full screen / with the steps list"),
+            ("< / >", "Narrow / widen the steps pane"),
+            ("F5", "Reload the steps file"),
+            ("o", "Open the complete step log in $PAGER (with --max-log-kb)"),
+            ("Up / Down", "Scroll the log"),
+            ("PgUp / PgDn", "Scroll the log faster"),
+            (":", "Command palette: find an action by name and run it"),
+            ("?", "Show this help"),
+            ("S", "Show the run summary"),
+            ("q", "Show the run summary, then q again to quit"),
+            ("Ctrl-C", "Quit right away"),
+        ],
+    ),
+    (
+        "App selection",
+        &[
+            ("Up / Down", "Move the cursor"),
+            ("Space", "Toggle the app under the cursor"),
+            ("a / A / i", "Select all / clear all / invert the selection"),
+            ("Enter", "Install the selected apps"),
+            ("Esc", "Cancel"),
+        ],
+    ),
+    (
+        "Git config / prompt forms",
+        &[
+            ("Tab / Shift+Tab", "Next / previous field"),
+            ("Type / Backspace", "Edit the current field"),
+            ("Enter", "Apply"),
+            ("Esc", "Cancel"),
+        ],
+    ),
+    (
+        "add_text preview",
+        &[("Enter", "Apply the edit"), ("Esc", "Cancel; the step stays pending")],
+    ),
+    (
+        "Summary",
+        &[
+            ("Up / Down", "Select a step"),
+            ("Enter", "Open the step's log"),
+            ("Esc", "Back to the main view"),
+            ("q", "Quit"),
+        ],
+    ),
+    ("Failure details", &[("Esc", "Back to the main view")]),
+    ("Explain", &[("Up / Down", "Scroll"), ("Esc / e", "Back to the main view")]),
+];
+
+fn ui_help(f: &mut ratatui::Frame<>, app: &App) {
+    let mut lines = Vec::new();
+    for (section, keys) in HELP_SECTIONS {
+        lines.push(Line::from(Span::styled(
+            *section,
+            fg(app.color, app.theme.accent),
+        )));
+        for (key, action) in *keys {
+            lines.push(Line::from(format!("  {:<18} {}", key, action)));
+        }
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from("Press any key to close."));
+
+    let panel = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Help"));
+
+    f.render_widget(panel, f.area());
+}
+
+fn ui_summary(f: &mut ratatui::Frame<>, app: &App, cursor: usize) {
+    let width = app
+        .steps
+        .iter()
+        .map(|step| step.name.width())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = app
+        .steps
+        .iter()
+        .zip(&app.runtimes)
+        .enumerate()
+        .map(|(idx, (step, rt))| {
+            let style = app.theme.status(rt.status);
+            let prefix = if idx == cursor { app.theme.cursor.as_str() } else { "" };
+            let prefix = pad(prefix, app.theme.cursor.width().max(1));
+            let duration = app.durations[idx]
+                .map(|d| format!("{:.1}s", d.as_secs_f64()))
+                .unwrap_or_else(|| "-".to_string());
+            let tail = rt.log.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("");
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", prefix)),
+                Span::styled(
+                    format!(
+                        "{} {:<8}",
+                        pad(&style.symbol, app.theme.symbol_width()),
+                        rt.status.label()
+                    ),
+                    fg(app.color, style.color),
+                ),
+                Span::raw(format!(" {} {:>7}  {}", pad(&step.name, width), duration, tail.trim())),
+            ]))
+        })
+        .collect();
+
+    let title = format!(
+        "Summary: {} (Up/Down=select, Enter=open log, Esc=back, q=quit)",
+        notify::summary(&app.steps, &app.runtimes)
+    );
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    let mut state = ListState::default().with_selected(Some(cursor));
+    f.render_stateful_widget(list, f.area(), &mut state);
+}
+
+fn ui_step_selection(f: &mut ratatui::Frame<>, app: &App, state: &StepSelectionState) {
+    let items: Vec<ListItem> = app
+        .steps
+        .iter()
+        .zip(&state.selected)
+        .enumerate()
+        .map(|(idx, (step, &on))| {
+            let cursor = if idx == state.cursor { app.theme.cursor.as_str() } else { "" };
+            let cursor = pad(cursor, app.theme.cursor.width().max(1));
+            let mark = if on { "[x]" } else { "[ ]" };
+            let tag = format!("{:<width$}", format!("[{}]", step.kind.tag()), width = TAG_WIDTH);
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} {} ", cursor, mark)),
+                Span::styled(tag, fg(app.color, app.theme.tag(&step.kind))),
+                Span::raw(format!(" {}", step.name)),
+            ]))
+        })
+        .collect();
+    let chosen = state.selected.iter().filter(|&&on| on).count();
+    let title = format!(
+        "Steps to run: {}/{} (Space=toggle, a=all, A=none, i=invert, Enter=start, q=abort)",
+        chosen,
+        state.selected.len()
+    );
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    let mut list_state = ListState::default().with_selected(Some(state.cursor));
+    f.render_stateful_widget(list, f.area(), &mut list_state);
+}
+
+fn ui_intro(f: &mut ratatui::Frame<>, app: &App, warnings: &[String]) {
+    let mut lines = vec![
+        Line::from(format!("{} step(s) will be offered in this order:", app.steps.len())),
+        Line::from(""),
+    ];
+    for (idx, step) in app.steps.iter().enumerate() {
+        lines.push(Line::from(format!(
+            "  {:>2}. {} [{}]",
+            idx + 1,
+            step.name,
+            step.kind.label()
+        )));
+    }
+
+    lines.push(Line::from(""));
+    if warnings.is_empty() {
+        lines.push(Line::from("No validation warnings."));
+    } else {
+        lines.push(Line::from(Span::styled(
+            "Warnings:",
+            fg(app.color, app.theme.accent),
+        )));
+        for warning in warnings {
+            lines.push(Line::from(format!("  - {}", warning)));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from("Press Enter to begin, q to abort."));
+
+    let panel = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("wiza-rs"))
+        .wrap(ratatui::widgets::Wrap { trim: false });
+
+    f.render_widget(panel, f.area());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtimes(statuses: &[StepStatus]) -> Vec<StepRuntime> {
+        statuses
+            .iter()
+            .map(|&status| StepRuntime { status, ..StepRuntime::default() })
+            .collect()
+    }
+
+    #[test]
+    fn hide_done_shows_only_steps_left_to_run() {
+        use StepStatus::*;
+        let rts = runtimes(&[Success, Pending, Skipped, Failed, Running]);
+        assert_eq!(visible_rows(&rts, false), vec![0, 1, 2, 3, 4]);
+        assert_eq!(visible_rows(&rts, true), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn all_skipped_with_hide_done_leaves_nothing_visible() {
+        use StepStatus::*;
+        let rts = runtimes(&[Skipped, Success, Skipped]);
+        assert!(visible_rows(&rts, true).is_empty());
+        // The cursor stays in range, so `runtimes[current]` is still safe.
+        assert_eq!(clamp_to_visible(&[], 2), 2);
+    }
+
+    #[test]
+    fn cursor_moves_to_the_next_visible_step_or_the_last() {
+        assert_eq!(clamp_to_visible(&[1, 3, 4], 3), 3);
+        assert_eq!(clamp_to_visible(&[1, 3, 4], 0), 1);
+        assert_eq!(clamp_to_visible(&[1, 3, 4], 2), 3);
+        assert_eq!(clamp_to_visible(&[1, 3], 4), 3);
+    }
+
+    #[test]
+    fn cjk_step_names_are_measured_in_columns() {
+        assert_eq!(pad("設定", 6), "設定  ");
+        assert_eq!(pad("設定", 6).width(), 6);
+        assert_eq!(take_width("日本語テキスト", 5), "日本");
+        assert_eq!(take_width("e\u{301}e\u{301}", 1), "e\u{301}");
+        let name = shorten("日本語のステップ名", 7);
+        assert_eq!(name, "日本語…");
+        assert!(name.width() <= 7);
+        assert_eq!(wrap_width("日本語abc", 4), vec!["日本", "語ab", "c"]);
+        let cmd = truncate_command("インストール 日本語", 10);
+        assert_eq!(cmd, "インスト…");
+        assert!(cmd.width() <= 10);
+    }
+}